@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use crate::{
+    preflight::CheckStatus,
+    provision::{Command, RemoteCommandExecutor},
+    static_pods::run_script,
+};
+
+// Restart control plane static pods, then wait for the local api server.
+const RESTART_STATIC_PODS_SCRIPT: &str = r#"
+trap start_static_pods EXIT
+stop_static_pods '^(etcd|kube-apiserver|kube-controller-manager|kube-scheduler)$'
+start_static_pods
+trap - EXIT
+
+for _ in $(seq 60); do
+    curl --silent --fail --insecure https://127.0.0.1:6443/livez > /dev/null && exit 0
+    sleep 5
+done
+echo "api server did not become live" >&2
+exit 1
+"#;
+
+/// Certificate managed by kubeadm as reported by `kubeadm certs check-expiration`.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    pub name: String,
+    /// Expiration date as printed by kubeadm like "Dec 30, 2026 23:36 UTC". None if missing.
+    pub expires: Option<String>,
+    /// Time left until expiration. None if expired or missing.
+    pub residual: Option<Duration>,
+    pub authority: bool,
+    pub externally_managed: bool,
+}
+
+impl Certificate {
+    /// Fail if expired or missing, Warn if it expires within the threshold.
+    pub fn status(&self, warn_within: Duration) -> CheckStatus {
+        match self.residual {
+            None => CheckStatus::Fail,
+            Some(residual) if residual < warn_within => CheckStatus::Warn,
+            Some(_) => CheckStatus::Pass,
+        }
+    }
+}
+
+/// Run kubeadm certificate commands on a master.
+pub struct Certs<Executor> {
+    executor: Executor,
+}
+
+impl<Executor> Certs<Executor> {
+    pub fn new(executor: Executor) -> Self {
+        Self { executor }
+    }
+}
+
+impl<Executor> Certs<Executor>
+where
+    Executor: RemoteCommandExecutor,
+{
+    pub async fn check_expiration(&self) -> anyhow::Result<Vec<Certificate>> {
+        let command = ["kubeadm", "certs", "check-expiration"];
+        let output = self
+            .executor
+            .stdout(Command::Sudo(&command), &command.join(" "))
+            .await?;
+        Ok(parse_check_expiration(&String::from_utf8_lossy(&output)))
+    }
+
+    /// Renew every certificate and restart control plane static pods to load them.
+    pub async fn renew(&self) -> anyhow::Result<()> {
+        let command = ["kubeadm", "certs", "renew", "all"];
+        self.executor
+            .stdout(Command::Sudo(&command), &command.join(" "))
+            .await?;
+        run_script(
+            &self.executor,
+            "restart static pods",
+            RESTART_STATIC_PODS_SCRIPT,
+            &[],
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Parse the tables printed by `kubeadm certs check-expiration`.
+/// Columns are aligned with at least two spaces while the expiration date contains single spaces.
+fn parse_check_expiration(output: &str) -> Vec<Certificate> {
+    let mut authority = None;
+    let mut certificates = Vec::new();
+
+    for line in output.lines() {
+        if line.starts_with("CERTIFICATE AUTHORITY") {
+            authority = Some(true);
+            continue;
+        }
+        if line.starts_with("CERTIFICATE") {
+            authority = Some(false);
+            continue;
+        }
+        // lines before the first table are log messages.
+        let authority = match authority {
+            Some(authority) => authority,
+            None => continue,
+        };
+
+        let columns = line
+            .split("  ")
+            .map(str::trim)
+            .filter(|column| !column.is_empty())
+            .collect::<Vec<_>>();
+        let name = match columns.first() {
+            Some(name) => *name,
+            None => continue,
+        };
+
+        let certificate = match name.strip_prefix("!MISSING!") {
+            Some(name) => Certificate {
+                name: name.trim().to_owned(),
+                expires: None,
+                residual: None,
+                authority,
+                externally_managed: false,
+            },
+            None => Certificate {
+                name: name.to_owned(),
+                expires: columns.get(1).map(|expires| (*expires).to_owned()),
+                residual: columns.get(2).and_then(|residual| parse_residual(residual)),
+                authority,
+                externally_managed: columns.last() == Some(&"yes"),
+            },
+        };
+        certificates.push(certificate);
+    }
+
+    certificates
+}
+
+/// Parse the short human duration of kubeadm like 364d or 9y. `<invalid>` means expired.
+fn parse_residual(residual: &str) -> Option<Duration> {
+    let unit = residual.chars().last()?;
+    let value = residual[..residual.len() - unit.len_utf8()]
+        .parse::<u64>()
+        .ok()?;
+    let seconds = match unit {
+        's' => value,
+        'm' => value * 60,
+        'h' => value * 60 * 60,
+        'd' => value * 24 * 60 * 60,
+        'y' => value * 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // kubeadm 1.32 with an expired apiserver certificate and a missing super-admin.conf.
+    const CHECK_EXPIRATION: &str = "\
+[check-expiration] Reading configuration from the cluster...
+[check-expiration] FYI: You can look at this config file with 'kubectl -n kube-system get cm kubeadm-config -o yaml'
+
+CERTIFICATE                EXPIRES                  RESIDUAL TIME   CERTIFICATE AUTHORITY   EXTERNALLY MANAGED
+admin.conf                 Dec 30, 2026 23:36 UTC   364d            ca                      no
+apiserver                  Jan 02, 2025 10:12 UTC   <invalid>       ca                      no
+apiserver-etcd-client      Dec 30, 2026 23:36 UTC   364d            etcd-ca                 no
+front-proxy-client         Dec 30, 2026 23:36 UTC   364d            front-proxy-ca          no
+!MISSING! super-admin.conf                                                                  
+
+CERTIFICATE AUTHORITY   EXPIRES                  RESIDUAL TIME   EXTERNALLY MANAGED
+ca                      Dec 28, 2035 23:36 UTC   9y              no
+etcd-ca                 Dec 28, 2035 23:36 UTC   9y              yes
+";
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    #[test]
+    fn parse_check_expiration_tables() {
+        let certificates = parse_check_expiration(CHECK_EXPIRATION);
+        let names = certificates
+            .iter()
+            .map(|certificate| certificate.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "admin.conf",
+                "apiserver",
+                "apiserver-etcd-client",
+                "front-proxy-client",
+                "super-admin.conf",
+                "ca",
+                "etcd-ca"
+            ]
+        );
+
+        let admin = &certificates[0];
+        assert_eq!(admin.expires.as_deref(), Some("Dec 30, 2026 23:36 UTC"));
+        assert_eq!(admin.residual, Some(Duration::from_secs(364 * DAY)));
+        assert!(!admin.authority);
+        assert!(!admin.externally_managed);
+
+        let ca = &certificates[5];
+        assert_eq!(ca.residual, Some(Duration::from_secs(9 * 365 * DAY)));
+        assert!(ca.authority);
+        assert!(!ca.externally_managed);
+        assert!(certificates[6].externally_managed);
+    }
+
+    #[test]
+    fn parse_check_expiration_invalid_and_missing() {
+        let certificates = parse_check_expiration(CHECK_EXPIRATION);
+        let warn_within = Duration::from_secs(30 * DAY);
+
+        let expired = &certificates[1];
+        assert_eq!(expired.expires.as_deref(), Some("Jan 02, 2025 10:12 UTC"));
+        assert_eq!(expired.residual, None);
+        assert_eq!(expired.status(warn_within), CheckStatus::Fail);
+
+        let missing = &certificates[4];
+        assert_eq!(missing.expires, None);
+        assert_eq!(missing.residual, None);
+        assert_eq!(missing.status(warn_within), CheckStatus::Fail);
+
+        assert_eq!(certificates[0].status(warn_within), CheckStatus::Pass);
+        assert_eq!(
+            certificates[0].status(Duration::from_secs(365 * DAY)),
+            CheckStatus::Warn
+        );
+    }
+
+    #[test]
+    fn parse_check_expiration_without_tables() {
+        assert!(parse_check_expiration("[check-expiration] error\n").is_empty());
+    }
+}
@@ -1,13 +1,29 @@
+mod certs;
+mod doctor;
+mod etcd;
 mod exec;
+mod fetch;
 mod node_state;
 mod provision;
+mod report;
+mod reset;
+mod schedule;
+mod ssh;
+mod ssh_config;
 mod status;
+mod upgrade;
 
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 
-use crate::{cli, node::NodeRole, Config};
+use crate::{
+    cli,
+    node::{NodeRole, NodeSelector, NodeTarget, TagSelector, EC2},
+    rollout::{Rollout, RolloutStrategy},
+    ssh::SessionPool,
+    usecase, Config,
+};
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -31,11 +47,93 @@ impl Cli {
 
     pub async fn run(self, config: Config) -> anyhow::Result<()> {
         match self.command {
-            Command::Provision { .. } => cli::provision::run(config).await,
-            Command::Status { .. } => cli::status::run(config, std::io::stdout()).await,
-            Command::Start { .. } => cli::node_state::start(config).await,
-            Command::Stop { .. } => cli::node_state::stop(config).await,
-            Command::Exec { command, role } => cli::exec::run(config, command, role).await,
+            Command::Provision {
+                selector,
+                rollout,
+                skip_preflight,
+                skip_verify,
+                verify_timeout,
+            } => {
+                let verify_timeout = (!skip_verify).then(|| Duration::from_secs(verify_timeout));
+                cli::provision::run(
+                    config,
+                    selector.into(),
+                    rollout,
+                    skip_preflight,
+                    verify_timeout,
+                )
+                .await
+            }
+            Command::Doctor { selector } => cli::doctor::run(config, selector.into()).await,
+            Command::Reset {
+                selector,
+                purge,
+                drain_timeout,
+                yes,
+            } => {
+                let drain_timeout = Duration::from_secs(drain_timeout);
+                cli::reset::run(config, selector.into(), purge, drain_timeout, yes).await
+            }
+            Command::Upgrade {
+                to,
+                drain_timeout,
+                health_timeout,
+            } => {
+                cli::upgrade::run(
+                    config,
+                    to,
+                    Duration::from_secs(drain_timeout),
+                    Duration::from_secs(health_timeout),
+                )
+                .await
+            }
+            Command::Status {
+                output,
+                watch,
+                interval,
+                until,
+                timeout,
+                kubernetes,
+            } => {
+                let watch = (watch || until.is_some()).then(|| cli::status::Watch {
+                    interval: Duration::from_secs(interval),
+                    until,
+                    timeout: timeout.map(Duration::from_secs),
+                });
+                let options = cli::status::StatusOptions {
+                    output,
+                    watch,
+                    kubernetes,
+                };
+                cli::status::run(config, options, std::io::stdout()).await
+            }
+            Command::Drain { target, timeout } => {
+                cli::schedule::drain(config, target, Duration::from_secs(timeout)).await
+            }
+            Command::Cordon { target } => cli::schedule::cordon(config, target).await,
+            Command::Uncordon { target } => cli::schedule::uncordon(config, target).await,
+            Command::Start { graceful, timeout } => {
+                let graceful = graceful.then(|| Duration::from_secs(timeout));
+                cli::node_state::start(config, graceful).await
+            }
+            Command::Stop {
+                graceful,
+                drain_timeout,
+                timeout,
+            } => {
+                let graceful = graceful.then(|| Duration::from_secs(timeout));
+                cli::node_state::stop(config, Duration::from_secs(drain_timeout), graceful).await
+            }
+            Command::Exec {
+                command,
+                selector,
+                rollout,
+            } => cli::exec::run(config, command, selector.into(), rollout).await,
+            Command::Certs { command } => cli::certs::run(config, command).await,
+            Command::Etcd { command } => cli::etcd::run(config, command).await,
+            Command::Fetch { target } => cli::fetch::run(config, target).await,
+            Command::Ssh { target } => cli::ssh::run(config, target).await,
+            Command::SshConfig { path, print } => cli::ssh_config::run(config, path, print).await,
         }
     }
 }
@@ -43,18 +141,257 @@ impl Cli {
 #[derive(Subcommand, Debug)]
 enum Command {
     #[clap(about = "Provision kubernetes nodes")]
-    Provision {},
+    Provision {
+        #[clap(flatten)]
+        selector: SelectorArgs,
+        #[clap(flatten)]
+        rollout: RolloutArgs,
+        #[clap(long, help = "do not run preflight checks before provisioning")]
+        skip_preflight: bool,
+        #[clap(long, help = "do not verify the cluster after provisioning")]
+        skip_verify: bool,
+        #[clap(
+            long,
+            default_value = "600",
+            help = "seconds to wait for nodes and pods to become ready in verification"
+        )]
+        verify_timeout: u64,
+    },
+    #[clap(about = "Check that nodes meet the requirements of kubeadm")]
+    Doctor {
+        #[clap(flatten)]
+        selector: SelectorArgs,
+    },
+    #[clap(about = "Remove kubernetes from nodes with kubeadm reset")]
+    Reset {
+        #[clap(flatten)]
+        selector: SelectorArgs,
+        #[clap(long, help = "also uninstall kubelet, kubeadm and kubectl")]
+        purge: bool,
+        #[clap(
+            long,
+            default_value = "120",
+            help = "seconds to wait for each worker to be drained"
+        )]
+        drain_timeout: u64,
+        #[clap(long, short = 'y', help = "do not ask for confirmation")]
+        yes: bool,
+    },
+    #[clap(about = "Upgrade kubernetes to the next minor version with kubeadm")]
+    Upgrade {
+        #[clap(long, help = "kubernetes minor version to upgrade to like 1.33")]
+        to: String,
+        #[clap(
+            long,
+            default_value = "120",
+            help = "seconds to wait for each node to be drained"
+        )]
+        drain_timeout: u64,
+        #[clap(
+            long,
+            default_value = "300",
+            help = "seconds to wait for each node to become Ready after the upgrade"
+        )]
+        health_timeout: u64,
+    },
     #[clap(about = "Print current nodes status")]
-    Status,
+    Status {
+        #[clap(
+            long,
+            short = 'o',
+            default_value = "table",
+            possible_values = ["table", "wide", "json", "yaml"],
+            help = "output format"
+        )]
+        output: cli::status::OutputFormat,
+        #[clap(long, short = 'w', help = "poll and redraw status until interrupted")]
+        watch: bool,
+        #[clap(
+            long,
+            default_value = "5",
+            help = "seconds between polls in watch mode"
+        )]
+        interval: u64,
+        #[clap(
+            long,
+            possible_values = ["running", "stopped"],
+            help = "watch until all nodes are in the state"
+        )]
+        until: Option<String>,
+        #[clap(
+            long,
+            requires = "until",
+            help = "seconds to wait for --until before failing"
+        )]
+        timeout: Option<u64>,
+        #[clap(
+            long,
+            short = 'k',
+            help = "also show kubernetes node status gathered from a master"
+        )]
+        kubernetes: bool,
+    },
+    #[clap(about = "Cordon nodes and evict their pods. DaemonSet pods are left running")]
+    Drain {
+        #[clap(help = "instance id, master, worker or role with index like worker-1")]
+        target: NodeTarget,
+        #[clap(
+            long,
+            default_value = "120",
+            help = "seconds to wait for each node to be drained"
+        )]
+        timeout: u64,
+    },
+    #[clap(about = "Mark nodes unschedulable")]
+    Cordon {
+        #[clap(help = "instance id, master, worker or role with index like worker-1")]
+        target: NodeTarget,
+    },
+    #[clap(about = "Mark nodes schedulable")]
+    Uncordon {
+        #[clap(help = "instance id, master, worker or role with index like worker-1")]
+        target: NodeTarget,
+    },
     #[clap(about = "Start kubernetes nodes")]
-    Start,
+    Start {
+        #[clap(
+            long,
+            help = "start masters first and workers once the api server responds, then uncordon workers"
+        )]
+        graceful: bool,
+        #[clap(
+            long,
+            default_value = "600",
+            help = "seconds to wait for each step in graceful mode"
+        )]
+        timeout: u64,
+    },
     #[clap(about = "Stop kubernetes nodes")]
-    Stop,
+    Stop {
+        #[clap(
+            long,
+            help = "drain and stop workers first, then stop masters after workers have stopped"
+        )]
+        graceful: bool,
+        #[clap(
+            long,
+            default_value = "120",
+            help = "seconds to wait for each worker to be drained in graceful mode"
+        )]
+        drain_timeout: u64,
+        #[clap(
+            long,
+            default_value = "600",
+            help = "seconds to wait for each step in graceful mode"
+        )]
+        timeout: u64,
+    },
     #[clap(about = "Execute given command in nodes")]
     Exec {
         #[clap(long, short = 'c', help = "execute command in bash -c ")]
         command: String,
-        #[clap(long, help = "target node role")]
-        role: Option<NodeRole>,
+        #[clap(flatten)]
+        selector: SelectorArgs,
+        #[clap(flatten)]
+        rollout: RolloutArgs,
+    },
+    #[clap(about = "Check and renew kubeadm certificates of masters")]
+    Certs {
+        #[clap(subcommand)]
+        command: cli::certs::CertsCommand,
+    },
+    #[clap(about = "Back up and restore etcd of the control plane")]
+    Etcd {
+        #[clap(subcommand)]
+        command: cli::etcd::EtcdCommand,
+    },
+    #[clap(about = "Fetch files from nodes")]
+    Fetch {
+        #[clap(subcommand)]
+        target: cli::fetch::FetchTarget,
+    },
+    #[clap(about = "Open interactive ssh session to a node")]
+    Ssh {
+        #[clap(help = "instance id, master, worker or role with index like worker-1")]
+        target: NodeTarget,
     },
+    #[clap(about = "Write ssh_config Host entries for nodes")]
+    SshConfig {
+        #[clap(long, help = "ssh config file to update. defaults to ~/.ssh/config")]
+        path: Option<PathBuf>,
+        #[clap(long, help = "print entries to stdout instead of updating the file")]
+        print: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct SelectorArgs {
+    #[clap(long, help = "target node role")]
+    role: Option<NodeRole>,
+    #[clap(
+        long,
+        help = "target node by instance id, role, index like worker-1 or range like worker[0:2]"
+    )]
+    node: Vec<NodeTarget>,
+    #[clap(long, help = "exclude node. same format as --node")]
+    exclude: Vec<NodeTarget>,
+    #[clap(
+        long,
+        help = "target node by tag like key=value. az=<zone> matches availability zone"
+    )]
+    selector: Vec<TagSelector>,
+}
+
+impl From<SelectorArgs> for NodeSelector {
+    fn from(args: SelectorArgs) -> Self {
+        NodeSelector {
+            role: args.role,
+            include: args.node,
+            exclude: args.exclude,
+            tags: args.selector,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RolloutArgs {
+    #[clap(long, help = "maximum number of nodes running at the same time")]
+    parallel: Option<usize>,
+    #[clap(long, help = "roll out to nodes in batches of this size")]
+    batch_size: Option<usize>,
+    #[clap(long, default_value = "0", help = "seconds to wait between batches")]
+    batch_pause: u64,
+    #[clap(
+        long,
+        help = "command which must succeed on every node of a batch before the next batch starts"
+    )]
+    health_check: Option<String>,
+    #[clap(
+        long,
+        default_value = "300",
+        help = "seconds to wait for the health check to succeed"
+    )]
+    health_timeout: u64,
+    #[clap(long, help = "cancel remaining nodes once any node fails")]
+    fail_fast: bool,
+}
+
+impl RolloutArgs {
+    fn into_rollout(self, sessions: &Arc<SessionPool>) -> Rollout<EC2> {
+        let rollout = Rollout::new(RolloutStrategy {
+            parallel: self.parallel,
+            batch_size: self.batch_size,
+            batch_pause: Duration::from_secs(self.batch_pause),
+            fail_fast: self.fail_fast,
+        });
+
+        match self.health_check {
+            Some(command) => rollout.health_gate(usecase::ec2::command_health_gate(
+                Arc::clone(sessions),
+                command,
+                Duration::from_secs(self.health_timeout),
+            )),
+            None => rollout,
+        }
+    }
 }
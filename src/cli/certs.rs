@@ -0,0 +1,144 @@
+use std::{io::Write, sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use clap::Subcommand;
+use prettytable::{cell, format, row, Table};
+
+use crate::{
+    certs::Certificate, cli::report, config::Provider, node::NodeId, operator::AwsOperator,
+    preflight::CheckStatus, ssh::SessionPool, usecase, Config,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum CertsCommand {
+    #[clap(about = "Print expiration of kubeadm certificates on each master")]
+    Check {
+        #[clap(
+            long,
+            default_value = "30",
+            help = "warn about certificates expiring within this many days"
+        )]
+        warn_days: u64,
+    },
+    #[clap(
+        about = "Renew kubeadm certificates and restart control plane pods one master at a time"
+    )]
+    Renew,
+}
+
+pub async fn run(config: Config, command: CertsCommand) -> anyhow::Result<()> {
+    match config.provider {
+        Provider::Aws => {
+            let operator = AwsOperator::from_config(&config).await?;
+            let sessions = Arc::new(SessionPool::new(
+                config.aws.as_ref().unwrap().ec2.node.ssh.clone(),
+            ));
+
+            match command {
+                CertsCommand::Check { warn_days } => {
+                    let results = usecase::ec2::certificates(&sessions, &operator).await?;
+                    let warn_within = Duration::from_secs(warn_days * 24 * 60 * 60);
+
+                    write_certificates(std::io::stdout(), &results, warn_within)?;
+
+                    let failed = results
+                        .iter()
+                        .filter(|(_, certificates)| match certificates {
+                            Ok(certificates) => certificates
+                                .iter()
+                                .any(|c| c.status(warn_within) == CheckStatus::Fail),
+                            Err(_) => true,
+                        })
+                        .count();
+                    if failed > 0 {
+                        return Err(anyhow!(
+                            "certificates are expired or missing on {failed} of {} masters",
+                            results.len()
+                        ));
+                    }
+                }
+                CertsCommand::Renew => {
+                    let report = usecase::ec2::renew_certificates(&sessions, &operator).await?;
+
+                    report::write_summary(std::io::stdout(), &report, "Result", |_| {
+                        "renewed".to_owned()
+                    })?;
+
+                    let failed = report.failed();
+                    if failed > 0 {
+                        return Err(anyhow!(
+                            "renew failed on {failed} of {} masters",
+                            report.executions.len()
+                        ));
+                    }
+                    if let Some(halted) = report.halted {
+                        return Err(halted);
+                    }
+                    // admin.conf is renewed as well.
+                    tracing::info!("run fetch kubeconfig to update the local kubeconfig");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_certificates(
+    mut w: impl Write,
+    results: &[(NodeId, anyhow::Result<Vec<Certificate>>)],
+    warn_within: Duration,
+) -> anyhow::Result<()> {
+    let mut table = Table::new();
+    let format = format::FormatBuilder::new().column_separator(' ').build();
+
+    table.set_format(format);
+    table.set_titles(row![
+        "InstanceId",
+        "Certificate",
+        "Expires",
+        "Residual",
+        "Result"
+    ]);
+
+    for (node_id, certificates) in results {
+        let certificates = match certificates {
+            Ok(certificates) => certificates,
+            Err(err) => {
+                table.add_row(row![
+                    node_id,
+                    "-",
+                    "-",
+                    "-",
+                    format!("{} {err:#}", CheckStatus::Fail)
+                ]);
+                continue;
+            }
+        };
+        for certificate in certificates {
+            let mut name = certificate.name.clone();
+            if certificate.authority {
+                name.push_str(" (ca)");
+            }
+            // kubeadm does not renew externally managed certificates.
+            if certificate.externally_managed {
+                name.push_str(" (external)");
+            }
+            let residual = match certificate.residual {
+                Some(residual) => format!("{}d", residual.as_secs() / (24 * 60 * 60)),
+                None => "-".to_owned(),
+            };
+            table.add_row(row![
+                node_id,
+                name,
+                certificate.expires.as_deref().unwrap_or("missing"),
+                residual,
+                certificate.status(warn_within),
+            ]);
+        }
+    }
+
+    table.print(&mut w)?;
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::{io::Write, sync::Arc};
+
+use anyhow::anyhow;
+use prettytable::{cell, format, row, Table};
+
+use crate::{
+    config::Provider, node::NodeSelector, operator::AwsOperator, preflight::NodePreflight,
+    ssh::SessionPool, usecase, Config,
+};
+
+pub async fn run(config: Config, selector: NodeSelector) -> anyhow::Result<()> {
+    match config.provider {
+        Provider::Aws => {
+            let operator = AwsOperator::from_config(&config).await?;
+            let sessions = Arc::new(SessionPool::new(
+                config.aws.as_ref().unwrap().ec2.node.ssh.clone(),
+            ));
+
+            check(&sessions, &operator, &selector).await
+        }
+    }
+}
+
+/// Run preflight checks, print the result and fail if any check failed.
+pub(super) async fn check(
+    sessions: &Arc<SessionPool>,
+    operator: &AwsOperator,
+    selector: &NodeSelector,
+) -> anyhow::Result<()> {
+    let results = usecase::ec2::preflight(sessions, operator, selector).await?;
+
+    write_checks(std::io::stdout(), &results)?;
+
+    let failed = results.iter().filter(|r| r.failed()).count();
+    if failed > 0 {
+        return Err(anyhow!(
+            "preflight checks failed on {failed} of {} nodes",
+            results.len()
+        ));
+    }
+
+    Ok(())
+}
+
+fn write_checks(mut w: impl Write, results: &[NodePreflight]) -> anyhow::Result<()> {
+    let mut table = Table::new();
+    let format = format::FormatBuilder::new().column_separator(' ').build();
+
+    table.set_format(format);
+    table.set_titles(row!["Role", "InstanceId", "Check", "Result", "Detail"]);
+
+    for result in results {
+        for check in result.checks.iter() {
+            table.add_row(row![
+                result.role,
+                result.node_id,
+                check.name,
+                check.status,
+                check.detail,
+            ]);
+        }
+    }
+
+    table.print(&mut w)?;
+
+    Ok(())
+}
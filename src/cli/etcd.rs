@@ -0,0 +1,88 @@
+use std::{io::Write, os::unix::fs::OpenOptionsExt, path::PathBuf, time::Duration};
+
+use anyhow::{anyhow, Context};
+use clap::Subcommand;
+
+use crate::{
+    cli::reset, config::Provider, operator::AwsOperator, ssh::SessionPool, usecase, Config,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum EtcdCommand {
+    #[clap(about = "Save an etcd snapshot on the first master and download it")]
+    Backup {
+        #[clap(
+            long,
+            default_value = ".",
+            help = "local directory to save the snapshot"
+        )]
+        dest: PathBuf,
+    },
+    #[clap(about = "Restore etcd of a single master cluster from a snapshot")]
+    Restore {
+        #[clap(help = "snapshot file saved by etcd backup")]
+        file: PathBuf,
+        #[clap(
+            long,
+            default_value = "300",
+            help = "seconds to wait for the api server after the restore"
+        )]
+        timeout: u64,
+        #[clap(long, short = 'y', help = "do not ask for confirmation")]
+        yes: bool,
+    },
+}
+
+pub async fn run(config: Config, command: EtcdCommand) -> anyhow::Result<()> {
+    match config.provider {
+        Provider::Aws => {
+            let operator = AwsOperator::from_config(&config).await?;
+            let sessions = SessionPool::new(config.aws.as_ref().unwrap().ec2.node.ssh.clone());
+
+            match command {
+                EtcdCommand::Backup { dest } => {
+                    let (file_name, content) =
+                        usecase::ec2::etcd_backup(&sessions, &operator).await?;
+
+                    std::fs::create_dir_all(&dest)?;
+                    let local_path = dest.join(format!("{}-{file_name}", config.cluster.name));
+                    // the snapshot holds every secret of the cluster.
+                    std::fs::OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .mode(0o600)
+                        .open(&local_path)
+                        .and_then(|mut file| file.write_all(&content))
+                        .with_context(|| format!("Could not write {local_path:?}"))?;
+
+                    tracing::info!("saved {local_path:?}");
+                }
+                EtcdCommand::Restore { file, timeout, yes } => {
+                    let snapshot =
+                        std::fs::read(&file).with_context(|| format!("Could not read {file:?}"))?;
+
+                    let prompt = format!(
+                        "this replaces etcd data of cluster {} with {file:?}",
+                        config.cluster.name
+                    );
+                    if !yes && !reset::confirm(&prompt, &config.cluster.name)? {
+                        return Err(anyhow!("restore cancelled"));
+                    }
+
+                    let previous = usecase::ec2::etcd_restore(
+                        &sessions,
+                        &operator,
+                        &snapshot,
+                        Duration::from_secs(timeout),
+                    )
+                    .await?;
+
+                    tracing::info!("restored etcd from {file:?}. previous data is kept in {previous} on the master");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,10 +1,64 @@
-use crate::{config::Provider, node::NodeRole, operator::AwsOperator, usecase, Config};
+use std::{io::Write, process::Output, sync::Arc};
 
-pub async fn run(config: Config, command: String, role: Option<NodeRole>) -> anyhow::Result<()> {
+use anyhow::anyhow;
+
+use crate::{
+    cli::{report, RolloutArgs},
+    config::Provider,
+    node::NodeSelector,
+    operator::AwsOperator,
+    rollout::{ExecOutcome, RolloutReport},
+    ssh::SessionPool,
+    usecase, Config,
+};
+
+pub async fn run(
+    config: Config,
+    command: String,
+    selector: NodeSelector,
+    rollout: RolloutArgs,
+) -> anyhow::Result<()> {
     match config.provider {
         Provider::Aws => {
             let operator = AwsOperator::from_config(&config).await?;
-            usecase::ec2::exec(config.aws.unwrap().ec2.node.ssh, operator, command, role).await
+            let sessions = Arc::new(SessionPool::new(config.aws.unwrap().ec2.node.ssh));
+            let rollout = rollout.into_rollout(&sessions);
+            let report =
+                usecase::ec2::exec(sessions, operator, command, &selector, rollout).await?;
+
+            let mut stdout = std::io::stdout();
+            write_outputs(&mut stdout, &report)?;
+            report::write_summary(&mut stdout, &report, "ExitCode", |output| {
+                output
+                    .status
+                    .code()
+                    .map(|code| code.to_string())
+                    .unwrap_or_else(|| "signal".to_owned())
+            })?;
+
+            let failed = report.failed();
+            if failed > 0 {
+                return Err(anyhow!(
+                    "command failed on {failed} of {} nodes",
+                    report.executions.len()
+                ));
+            }
+            if let Some(halted) = report.halted {
+                return Err(halted);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_outputs(mut w: impl Write, report: &RolloutReport<Output>) -> anyhow::Result<()> {
+    for execution in report.executions.iter() {
+        if let ExecOutcome::Completed(output) = &execution.outcome {
+            writeln!(w, "==> {} {} <==", execution.role, execution.node_id)?;
+            w.write_all(&output.stdout)?;
+            w.write_all(&output.stderr)?;
         }
     }
+    Ok(())
 }
@@ -0,0 +1,83 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::anyhow;
+use clap::Subcommand;
+
+use crate::{
+    cli::SelectorArgs, config::Provider, kubeconfig, kubeconfig::Kubeconfig, operator::AwsOperator,
+    ssh::SessionPool, usecase, Config,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum FetchTarget {
+    #[clap(about = "Fetch admin kubeconfig from master and merge it into local kubeconfig")]
+    Kubeconfig {
+        #[clap(
+            long,
+            help = "kubeconfig to merge into. defaults to $KUBECONFIG or ~/.kube/config"
+        )]
+        kubeconfig: Option<PathBuf>,
+    },
+    #[clap(about = "Fetch a file from nodes into <dest>/<node-id>/")]
+    File {
+        #[clap(long, short = 'p', help = "remote file path")]
+        path: String,
+        #[clap(flatten)]
+        selector: SelectorArgs,
+        #[clap(long, default_value = ".", help = "local directory to save files")]
+        dest: PathBuf,
+    },
+}
+
+pub async fn run(config: Config, target: FetchTarget) -> anyhow::Result<()> {
+    match config.provider {
+        Provider::Aws => {
+            let operator = AwsOperator::from_config(&config).await?;
+            let sessions = Arc::new(SessionPool::new(
+                config.aws.as_ref().unwrap().ec2.node.ssh.clone(),
+            ));
+
+            match target {
+                FetchTarget::Kubeconfig { kubeconfig } => {
+                    let path = match kubeconfig {
+                        Some(path) => path,
+                        None => kubeconfig::default_path()?,
+                    };
+                    let fetched =
+                        usecase::ec2::fetch_kubeconfig(&config.cluster, &sessions, &operator)
+                            .await?;
+
+                    let mut local = Kubeconfig::load(&path)?;
+                    local.merge(fetched);
+                    local.save(&path)?;
+
+                    tracing::info!("merged context {} into {path:?}", config.cluster.name);
+                }
+                FetchTarget::File {
+                    path,
+                    selector,
+                    dest,
+                } => {
+                    let file_name = PathBuf::from(&path)
+                        .file_name()
+                        .map(PathBuf::from)
+                        .ok_or_else(|| anyhow!("path {path} does not have file name"))?;
+                    let files =
+                        usecase::ec2::fetch_files(sessions, &operator, path, &selector.into())
+                            .await?;
+
+                    for (node_id, content) in files {
+                        let dir = dest.join(node_id.as_ref());
+                        std::fs::create_dir_all(&dir)?;
+                        let local_path = dir.join(&file_name);
+                        std::fs::write(&local_path, content)?;
+
+                        tracing::info!("saved {local_path:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
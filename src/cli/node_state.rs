@@ -1,31 +1,60 @@
-use crate::{config::Provider, operator::AwsOperator, usecase, Config};
+use std::{sync::Arc, time::Duration};
+
+use crate::{config::Provider, operator::AwsOperator, ssh::SessionPool, usecase, Config};
 
 enum ChangeState {
     Start,
-    Stop,
+    Stop { drain_timeout: Duration },
 }
-pub async fn start(config: Config) -> anyhow::Result<()> {
-    change_node_state(ChangeState::Start, config).await
+
+/// graceful is the timeout of each step in graceful mode. None starts or stops every node at once.
+pub async fn start(config: Config, graceful: Option<Duration>) -> anyhow::Result<()> {
+    change_node_state(ChangeState::Start, config, graceful).await
 }
 
-pub async fn stop(config: Config) -> anyhow::Result<()> {
-    change_node_state(ChangeState::Stop, config).await
+pub async fn stop(
+    config: Config,
+    drain_timeout: Duration,
+    graceful: Option<Duration>,
+) -> anyhow::Result<()> {
+    change_node_state(ChangeState::Stop { drain_timeout }, config, graceful).await
 }
 
-async fn change_node_state(change: ChangeState, config: Config) -> anyhow::Result<()> {
+async fn change_node_state(
+    change: ChangeState,
+    config: Config,
+    graceful: Option<Duration>,
+) -> anyhow::Result<()> {
     match config.provider {
         Provider::Aws => {
             let operator = AwsOperator::from_config(&config).await?;
+            let sessions = Arc::new(SessionPool::new(
+                config.aws.as_ref().unwrap().ec2.node.ssh.clone(),
+            ));
             let operation: &str;
-            let nodes = match change {
-                ChangeState::Start => {
+            let nodes = match (change, graceful) {
+                (ChangeState::Start, None) => {
                     operation = "starting";
                     usecase::ec2::start_instances(&operator).await?
                 }
-                ChangeState::Stop => {
+                (ChangeState::Stop { .. }, None) => {
                     operation = "stopping";
                     usecase::ec2::stop_instances(&operator).await?
                 }
+                (ChangeState::Start, Some(timeout)) => {
+                    operation = "started";
+                    usecase::ec2::start_instances_gracefully(&sessions, &operator, timeout).await?
+                }
+                (ChangeState::Stop { drain_timeout }, Some(timeout)) => {
+                    operation = "stopped";
+                    usecase::ec2::stop_instances_gracefully(
+                        &sessions,
+                        &operator,
+                        drain_timeout,
+                        timeout,
+                    )
+                    .await?
+                }
             };
 
             tracing::info!("{operation} {:?}", nodes.node_ids().collect::<Vec<_>>());
@@ -1,10 +1,83 @@
-use crate::{config::Provider, operator::AwsOperator, usecase, Config};
+use std::{sync::Arc, time::Duration};
 
-pub async fn run(config: Config) -> anyhow::Result<()> {
+use anyhow::anyhow;
+
+use crate::{
+    cli::{doctor, report, RolloutArgs},
+    config::Provider,
+    node::NodeSelector,
+    operator::AwsOperator,
+    preflight::CheckStatus,
+    ssh::SessionPool,
+    usecase, Config,
+};
+
+pub async fn run(
+    config: Config,
+    selector: NodeSelector,
+    rollout: RolloutArgs,
+    skip_preflight: bool,
+    verify_timeout: Option<Duration>,
+) -> anyhow::Result<()> {
     match config.provider {
         Provider::Aws => {
             let operator = AwsOperator::from_config(&config).await?;
-            usecase::ec2::provision(&config.aws.unwrap().ec2.node.ssh, &operator).await
+            let node_config = &config.aws.as_ref().unwrap().ec2.node;
+            let sessions = Arc::new(SessionPool::new(node_config.ssh.clone()));
+            if !skip_preflight {
+                doctor::check(&sessions, &operator, &selector).await?;
+            }
+
+            let rollout = rollout.into_rollout(&sessions);
+            let report = usecase::ec2::provision(
+                &config.cluster,
+                node_config.distribution,
+                &sessions,
+                &operator,
+                &selector,
+                rollout,
+            )
+            .await?;
+
+            report::write_summary(std::io::stdout(), &report, "Result", |_| "ok".to_owned())?;
+
+            let failed = report.failed();
+            if failed > 0 {
+                return Err(anyhow!(
+                    "provision failed on {failed} of {} nodes",
+                    report.executions.len()
+                ));
+            }
+            if let Some(halted) = report.halted {
+                return Err(halted);
+            }
+
+            usecase::ec2::bootstrap(
+                &config.cluster,
+                node_config,
+                &sessions,
+                &operator,
+                &selector,
+            )
+            .await?;
+            usecase::ec2::label_nodes(node_config, &sessions, &operator, &selector).await?;
+
+            if let Some(timeout) = verify_timeout {
+                let checks = usecase::ec2::verify(&sessions, &operator, timeout).await?;
+                report::write_checks(std::io::stdout(), &checks)?;
+
+                let failed = checks
+                    .iter()
+                    .filter(|c| c.status == CheckStatus::Fail)
+                    .count();
+                if failed > 0 {
+                    return Err(anyhow!(
+                        "cluster verification failed: {failed} checks failed"
+                    ));
+                }
+            }
         }
     }
+
+    Ok(())
 }
@@ -0,0 +1,70 @@
+use std::io::Write;
+
+use prettytable::{cell, format, row, Table};
+
+use crate::{
+    preflight::Check,
+    rollout::{ExecOutcome, RolloutReport, TaskOutput},
+};
+
+/// Write per node summary of the rollout.
+/// result renders the task output for the result column.
+pub fn write_summary<R: TaskOutput>(
+    mut w: impl Write,
+    report: &RolloutReport<R>,
+    result_title: &str,
+    result: impl Fn(&R) -> String,
+) -> anyhow::Result<()> {
+    let mut table = Table::new();
+    let format = format::FormatBuilder::new().column_separator(' ').build();
+
+    table.set_format(format);
+    table.set_titles(row![
+        "Role",
+        "InstanceId",
+        result_title,
+        "Duration",
+        "Error"
+    ]);
+
+    for execution in report.executions.iter() {
+        let (result, error) = match &execution.outcome {
+            ExecOutcome::Completed(output) => (result(output), String::new()),
+            ExecOutcome::Error(err) => ("-".to_owned(), format!("{err:#}")),
+            ExecOutcome::Cancelled => ("-".to_owned(), "cancelled".to_owned()),
+            ExecOutcome::Skipped => ("-".to_owned(), "skipped".to_owned()),
+        };
+        table.add_row(row![
+            execution.role,
+            execution.node_id,
+            result,
+            format!("{:.1}s", execution.elapsed.as_secs_f64()),
+            error,
+        ]);
+    }
+
+    table.print(&mut w)?;
+
+    if let Some(halted) = report.halted.as_ref() {
+        writeln!(w, "rollout halted: {halted:#}")?;
+    }
+
+    Ok(())
+}
+
+/// Write cluster level checks such as post-provision verification.
+pub fn write_checks(mut w: impl Write, checks: &[Check]) -> anyhow::Result<()> {
+    let mut table = Table::new();
+    let format = format::FormatBuilder::new().column_separator(' ').build();
+
+    table.set_format(format);
+    table.set_titles(row!["Check", "Result", "Detail"]);
+
+    for check in checks {
+        table.add_row(row![check.name, check.status, check.detail]);
+    }
+
+    table.print(&mut w)?;
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::{
+    io::{BufRead, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    cli::report, config::Provider, node::NodeSelector, operator::AwsOperator, ssh::SessionPool,
+    usecase, Config,
+};
+
+pub async fn run(
+    config: Config,
+    selector: NodeSelector,
+    purge: bool,
+    drain_timeout: Duration,
+    yes: bool,
+) -> anyhow::Result<()> {
+    match config.provider {
+        Provider::Aws => {
+            let operator = AwsOperator::from_config(&config).await?;
+            let sessions = Arc::new(SessionPool::new(
+                config.aws.as_ref().unwrap().ec2.node.ssh.clone(),
+            ));
+
+            let prompt = format!(
+                "this removes kubernetes from nodes of cluster {}",
+                config.cluster.name
+            );
+            if !yes && !confirm(&prompt, &config.cluster.name)? {
+                return Err(anyhow!("reset cancelled"));
+            }
+
+            let report =
+                usecase::ec2::reset(&sessions, &operator, &selector, purge, drain_timeout).await?;
+
+            report::write_summary(std::io::stdout(), &report, "Result", |_| "reset".to_owned())?;
+
+            let failed = report.failed();
+            if failed > 0 {
+                return Err(anyhow!(
+                    "reset failed on {failed} of {} nodes",
+                    report.executions.len()
+                ));
+            }
+            if let Some(halted) = report.halted {
+                return Err(halted);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask to type the cluster name before a destructive operation.
+pub(super) fn confirm(prompt: &str, cluster_name: &str) -> anyhow::Result<bool> {
+    let mut stderr = std::io::stderr();
+    write!(stderr, "{prompt}. type the cluster name to continue: ")?;
+    stderr.flush()?;
+
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+
+    Ok(line.trim() == cluster_name)
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use crate::{
+    config::Provider,
+    node::NodeTarget,
+    operator::AwsOperator,
+    ssh::SessionPool,
+    usecase::{self, ec2::Schedule},
+    Config,
+};
+
+pub async fn drain(config: Config, target: NodeTarget, timeout: Duration) -> anyhow::Result<()> {
+    change_schedule(Schedule::Drain { timeout }, config, target).await
+}
+
+pub async fn cordon(config: Config, target: NodeTarget) -> anyhow::Result<()> {
+    change_schedule(Schedule::Cordon, config, target).await
+}
+
+pub async fn uncordon(config: Config, target: NodeTarget) -> anyhow::Result<()> {
+    change_schedule(Schedule::Uncordon, config, target).await
+}
+
+async fn change_schedule(
+    schedule: Schedule,
+    config: Config,
+    target: NodeTarget,
+) -> anyhow::Result<()> {
+    match config.provider {
+        Provider::Aws => {
+            let operator = AwsOperator::from_config(&config).await?;
+            let sessions = SessionPool::new(config.aws.as_ref().unwrap().ec2.node.ssh.clone());
+
+            let operation = match schedule {
+                Schedule::Drain { .. } => "drained",
+                Schedule::Cordon => "cordoned",
+                Schedule::Uncordon => "uncordoned",
+            };
+            let nodes = usecase::ec2::schedule(&sessions, &operator, &target, schedule).await?;
+            for (node_id, node_name) in nodes {
+                tracing::info!("{operation} {node_name} ({node_id})");
+            }
+        }
+    }
+
+    Ok(())
+}
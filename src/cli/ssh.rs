@@ -0,0 +1,61 @@
+use std::io::{BufRead, Write};
+
+use anyhow::anyhow;
+
+use crate::{
+    config::Provider,
+    node::{Node, NodeRole, NodeTarget, EC2},
+    operator::AwsOperator,
+    ssh, usecase, Config,
+};
+
+pub async fn run(config: Config, target: NodeTarget) -> anyhow::Result<()> {
+    match config.provider {
+        Provider::Aws => {
+            let operator = AwsOperator::from_config(&config).await?;
+            let cluster_nodes = usecase::ec2::collect(&operator).await?;
+
+            let node = match cluster_nodes.find(&target).as_slice() {
+                [] => return Err(anyhow!("node {target} not found")),
+                [(_, node)] => *node,
+                candidates => choose(candidates)?,
+            };
+            let public_ip = node.public_ip().ok_or_else(|| {
+                anyhow!(
+                    "node {} does not have public ip. maybe not started",
+                    node.id()
+                )
+            })?;
+
+            Err(ssh::interactive(
+                &config.aws.unwrap().ec2.node.ssh,
+                &public_ip.to_string(),
+            ))
+        }
+    }
+}
+
+fn choose<'a>(candidates: &[(NodeRole, &'a EC2)]) -> anyhow::Result<&'a EC2> {
+    let mut stderr = std::io::stderr();
+    for (i, (role, node)) in candidates.iter().enumerate() {
+        let public_ip = node
+            .public_ip()
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".to_owned());
+        writeln!(stderr, "[{i}] {role} {} {public_ip}", node.id())?;
+    }
+    write!(stderr, "select node: ")?;
+    stderr.flush()?;
+
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let index = line
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| anyhow!("invalid selection {:?}", line.trim()))?;
+
+    candidates
+        .get(index)
+        .map(|(_, node)| *node)
+        .ok_or_else(|| anyhow!("invalid selection {index}"))
+}
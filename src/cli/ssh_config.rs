@@ -0,0 +1,161 @@
+use std::{fmt::Write as _, io::Write, path::PathBuf};
+
+use anyhow::anyhow;
+
+use crate::{
+    config::{Provider, SshConfig},
+    node::{ClusterNodes, Node, EC2},
+    operator::AwsOperator,
+    ssh, usecase, Config,
+};
+
+pub async fn run(config: Config, path: Option<PathBuf>, print: bool) -> anyhow::Result<()> {
+    match config.provider {
+        Provider::Aws => {
+            let operator = AwsOperator::from_config(&config).await?;
+            let cluster_nodes = usecase::ec2::collect(&operator).await?;
+
+            let block = managed_block(
+                &config.cluster.name,
+                &config.aws.as_ref().unwrap().ec2.node.ssh,
+                &cluster_nodes,
+            );
+            if print {
+                std::io::stdout().write_all(block.as_bytes())?;
+                return Ok(());
+            }
+
+            let path = match path {
+                Some(path) => path,
+                None => default_path()?,
+            };
+            let current = match std::fs::read_to_string(&path) {
+                Ok(current) => current,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+                Err(err) => return Err(err.into()),
+            };
+            let updated = replace_block(&current, &config.cluster.name, &block);
+            if updated != current {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(&path, updated)?;
+            }
+
+            tracing::info!("updated {path:?}");
+        }
+    }
+
+    Ok(())
+}
+
+fn default_path() -> anyhow::Result<PathBuf> {
+    let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set"))?;
+
+    Ok(PathBuf::from(home).join(".ssh").join("config"))
+}
+
+fn begin_marker(cluster: &str) -> String {
+    format!("# BEGIN kubeprovision {cluster}")
+}
+
+fn end_marker(cluster: &str) -> String {
+    format!("# END kubeprovision {cluster}")
+}
+
+// Host entries are named <cluster>-<role>-<index> so they match `kubeprovision ssh <role>-<index>`.
+fn managed_block(cluster: &str, ssh_config: &SshConfig, nodes: &ClusterNodes<EC2>) -> String {
+    let mut block = begin_marker(cluster);
+    block.push('\n');
+
+    for (role, index, node) in nodes.indexed() {
+        let host = format!("{cluster}-{role}-{index}");
+
+        let public_ip = match node.public_ip() {
+            Some(ip) => ip,
+            None => {
+                tracing::warn!("skip {host}: node {} does not have public ip", node.id());
+                continue;
+            }
+        };
+
+        let _ = writeln!(block, "Host {host}");
+        let _ = writeln!(block, "    HostName {public_ip}");
+        let _ = writeln!(block, "    User {}", ssh_config.user);
+        for (key, value) in ssh::options(ssh_config) {
+            let _ = writeln!(block, "    {key} {value}");
+        }
+    }
+
+    block.push_str(&end_marker(cluster));
+    block.push('\n');
+    block
+}
+
+// Replace the existing managed block of the cluster or append it to the end.
+fn replace_block(current: &str, cluster: &str, block: &str) -> String {
+    let begin = begin_marker(cluster);
+    let end = end_marker(cluster);
+
+    // markers are matched as whole lines so that a cluster named like the prefix of another
+    // does not match its block.
+    let mut start = None;
+    let mut offset = 0;
+    for line in current.split_inclusive('\n') {
+        let next = offset + line.len();
+        let content = line.trim_end_matches('\n');
+        match start {
+            None if content == begin => start = Some(offset),
+            Some(start) if content == end => {
+                return format!("{}{block}{}", &current[..start], &current[next..]);
+            }
+            _ => {}
+        }
+        offset = next;
+    }
+
+    let mut updated = current.to_owned();
+    if !updated.is_empty() {
+        if !updated.ends_with('\n') {
+            updated.push('\n');
+        }
+        updated.push('\n');
+    }
+    updated.push_str(block);
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: &str = "# BEGIN kubeprovision dev\nHost dev-master-0\n# END kubeprovision dev\n";
+
+    #[test]
+    fn replace_block_appends() {
+        assert_eq!(replace_block("", "dev", BLOCK), BLOCK);
+        assert_eq!(
+            replace_block("Host other\n    User ubuntu", "dev", BLOCK),
+            format!("Host other\n    User ubuntu\n\n{BLOCK}")
+        );
+    }
+
+    #[test]
+    fn replace_block_replaces_existing() {
+        let current =
+            "Host a\n\n# BEGIN kubeprovision dev\nHost old\n# END kubeprovision dev\nHost b\n";
+        assert_eq!(
+            replace_block(current, "dev", BLOCK),
+            format!("Host a\n\n{BLOCK}Host b\n")
+        );
+    }
+
+    #[test]
+    fn replace_block_matches_whole_marker_lines() {
+        let current = "# BEGIN kubeprovision dev2\nHost dev2-master-0\n# END kubeprovision dev2\n";
+        assert_eq!(
+            replace_block(current, "dev", BLOCK),
+            format!("{current}\n{BLOCK}")
+        );
+    }
+}
@@ -1,45 +1,359 @@
-use std::io::Write;
+use std::{
+    collections::HashMap,
+    fmt,
+    fmt::Formatter,
+    io::{IsTerminal, Write},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
 
-use prettytable::{cell, format, row, Table};
+use prettytable::{format, Cell, Row, Table};
+use serde::Serialize;
 
 use crate::{
     config::Provider,
+    kubernetes::KubeNode,
     node::{Node, NodeRole, EC2},
     operator::AwsOperator,
+    ssh::SessionPool,
     usecase, Config,
 };
 
-pub async fn run(config: Config, writer: impl Write) -> anyhow::Result<()> {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Wide,
+    Json,
+    Yaml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "wide" => Ok(OutputFormat::Wide),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            etc => Err(format!("unexpected output format {etc}")),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Table => write!(f, "table"),
+            OutputFormat::Wide => write!(f, "wide"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Yaml => write!(f, "yaml"),
+        }
+    }
+}
+
+/// Document printed by `status --output json|yaml`. Fields must not be renamed or removed.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StatusDocument {
+    version: u32,
+    cluster: String,
+    nodes: Vec<NodeStatus>,
+}
+
+const STATUS_DOCUMENT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NodeStatus {
+    role: String,
+    instance_id: String,
+    state: String,
+    public_ip: Option<String>,
+    private_ip: Option<String>,
+    instance_type: Option<String>,
+    availability_zone: Option<String>,
+    launch_time: Option<String>,
+    role_tag: Option<String>,
+    /// Present only with `--kubernetes`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kubernetes: Option<KubernetesStatus>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct KubernetesStatus {
+    joined: bool,
+    node_name: Option<String>,
+    /// Status of the Ready condition. True, False or Unknown.
+    ready: Option<String>,
+    kubelet_version: Option<String>,
+    roles: Vec<String>,
+}
+
+impl From<Option<&KubeNode>> for KubernetesStatus {
+    fn from(node: Option<&KubeNode>) -> Self {
+        Self {
+            joined: node.is_some(),
+            node_name: node.map(|n| n.name.clone()),
+            ready: node.map(|n| n.ready.clone()),
+            kubelet_version: node.map(|n| n.kubelet_version.clone()),
+            roles: node.map(|n| n.roles.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+impl NodeStatus {
+    fn new(role: NodeRole, instance: &EC2, role_tag_key: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            instance_id: instance.id().to_string(),
+            state: instance.state().as_str().to_owned(),
+            public_ip: instance.public_ip().map(|ip| ip.to_string()),
+            private_ip: instance.private_ip().map(|ip| ip.to_string()),
+            instance_type: instance.instance_type().map(str::to_owned),
+            availability_zone: instance.availability_zone().map(str::to_owned),
+            launch_time: instance.launch_time(),
+            role_tag: instance
+                .tag(role_tag_key)
+                .map(|value| format!("{role_tag_key}={value}")),
+            kubernetes: None,
+        }
+    }
+}
+
+pub struct StatusOptions {
+    pub output: OutputFormat,
+    pub watch: Option<Watch>,
+    /// Also show the kubernetes view of nodes gathered from a master.
+    pub kubernetes: bool,
+}
+
+/// Options for `status --watch`.
+#[derive(Debug, Clone)]
+pub struct Watch {
+    pub interval: Duration,
+    /// Exit once all nodes are in this state.
+    pub until: Option<String>,
+    pub timeout: Option<Duration>,
+}
+
+pub async fn run(config: Config, options: StatusOptions, writer: impl Write) -> anyhow::Result<()> {
     match config.provider {
         Provider::Aws => {
             let operator = AwsOperator::from_config(&config).await?;
-            let ec2_nodes = usecase::ec2::collect(&operator).await?;
+            let sessions = options
+                .kubernetes
+                .then(|| SessionPool::new(config.aws.as_ref().unwrap().ec2.node.ssh.clone()));
+            let status = StatusCollector {
+                config: &config,
+                operator: &operator,
+                sessions: sessions.as_ref(),
+            };
 
-            write_status(writer, ec2_nodes.nodes())
+            match options.watch.as_ref() {
+                Some(watch) => watch_status(&status, &options, watch, writer).await,
+                None => {
+                    let document = status.collect().await?;
+                    write_status(writer, &options, &document, &HashMap::new())
+                }
+            }
         }
     }
 }
 
-fn write_status<'a>(
+struct StatusCollector<'a> {
+    config: &'a Config,
+    operator: &'a AwsOperator,
+    sessions: Option<&'a SessionPool>,
+}
+
+impl StatusCollector<'_> {
+    async fn collect(&self) -> anyhow::Result<StatusDocument> {
+        let ec2_nodes = usecase::ec2::collect(self.operator).await?;
+        let node_config = &self.config.aws.as_ref().unwrap().ec2.node;
+
+        // kubernetes view is best effort. masters may be stopped or not provisioned yet.
+        let kube_nodes = match self.sessions {
+            Some(sessions) => match usecase::ec2::kubernetes_nodes(sessions, &ec2_nodes).await {
+                Ok(kube_nodes) => Some(kube_nodes),
+                Err(err) => {
+                    tracing::warn!("Could not get kubernetes nodes: {err:#}");
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let nodes = ec2_nodes
+            .nodes()
+            .map(|(role, instance)| {
+                let role_tag_key = match role {
+                    NodeRole::Master => &node_config.master.tag.key,
+                    NodeRole::Worker => &node_config.worker.tag.key,
+                };
+                let mut status = NodeStatus::new(role, instance, role_tag_key);
+                status.kubernetes = kube_nodes
+                    .as_ref()
+                    .map(|kube_nodes| usecase::ec2::find_kube_node(kube_nodes, instance).into());
+                status
+            })
+            .collect();
+
+        Ok(StatusDocument {
+            version: STATUS_DOCUMENT_VERSION,
+            cluster: self.config.cluster.name.clone(),
+            nodes,
+        })
+    }
+}
+
+async fn watch_status(
+    status: &StatusCollector<'_>,
+    options: &StatusOptions,
+    watch: &Watch,
     mut w: impl Write,
-    instances: impl Iterator<Item = (NodeRole, &'a EC2)>,
 ) -> anyhow::Result<()> {
+    let table = matches!(options.output, OutputFormat::Table | OutputFormat::Wide);
+    let redraw = std::io::stdout().is_terminal();
+    let started = Instant::now();
+    // instance id => state at the previous poll
+    let mut previous = HashMap::<String, String>::new();
+
+    loop {
+        let document = status.collect().await?;
+        let transitions = document
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                previous
+                    .get(&node.instance_id)
+                    .filter(|state| **state != node.state)
+                    .map(|state| (node.instance_id.clone(), state.clone()))
+            })
+            .collect::<HashMap<_, _>>();
+
+        // json and yaml are written as a stream of documents which must stay parsable.
+        if table {
+            if redraw {
+                // clear screen and move cursor to top left.
+                write!(w, "\x1b[2J\x1b[H")?;
+            }
+            writeln!(
+                w,
+                "Every {}s: {:.0}s elapsed",
+                watch.interval.as_secs(),
+                started.elapsed().as_secs_f64()
+            )?;
+        }
+        write_status(&mut w, options, &document, &transitions)?;
+        w.flush()?;
+
+        if let Some(until) = watch.until.as_ref() {
+            if !document.nodes.is_empty() && document.nodes.iter().all(|n| &n.state == until) {
+                return Ok(());
+            }
+            if let Some(timeout) = watch.timeout {
+                if started.elapsed() >= timeout {
+                    return Err(anyhow!(
+                        "nodes did not become {until} within {}s",
+                        timeout.as_secs()
+                    ));
+                }
+            }
+        }
+
+        previous = document
+            .nodes
+            .into_iter()
+            .map(|node| (node.instance_id, node.state))
+            .collect();
+        tokio::time::sleep(watch.interval).await;
+    }
+}
+
+/// transitions maps instance id to the previous state for nodes whose state changed.
+fn write_status(
+    mut w: impl Write,
+    options: &StatusOptions,
+    document: &StatusDocument,
+    transitions: &HashMap<String, String>,
+) -> anyhow::Result<()> {
+    match options.output {
+        OutputFormat::Table => write_table(w, options, &document.nodes, transitions),
+        OutputFormat::Wide => write_table(w, options, &document.nodes, transitions),
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut w, document)?;
+            writeln!(w)?;
+            Ok(())
+        }
+        OutputFormat::Yaml => {
+            serde_yaml::to_writer(&mut w, document)?;
+            Ok(())
+        }
+    }
+}
+
+fn write_table(
+    mut w: impl Write,
+    options: &StatusOptions,
+    nodes: &[NodeStatus],
+    transitions: &HashMap<String, String>,
+) -> anyhow::Result<()> {
+    let wide = options.output == OutputFormat::Wide;
     let mut table = Table::new();
     let format = format::FormatBuilder::new().column_separator(' ').build();
 
     table.set_format(format);
-    table.set_titles(row!["Role", "InstanceId", "PublicIp", "State"]);
-
-    for (role, instance) in instances {
-        table.add_row(row![
-            role,
-            instance.id(),
-            instance
-                .public_ip()
-                .map(|ip| ip.to_string())
-                .unwrap_or_else(|| "unknown".to_owned()),
-            instance.state().as_str(),
-        ]);
+    let mut titles = vec!["Role", "InstanceId", "PublicIp", "State"];
+    if wide {
+        titles.extend(["PrivateIp", "InstanceType", "AZ", "LaunchTime", "RoleTag"]);
+    }
+    if options.kubernetes {
+        titles.extend(["Joined", "Ready", "Version", "K8sRoles"]);
+    }
+    table.set_titles(Row::new(titles.into_iter().map(Cell::new).collect()));
+
+    let or_unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_owned());
+    for node in nodes {
+        let state = match transitions.get(&node.instance_id) {
+            Some(previous) => format!("{previous} -> {}", node.state),
+            None => node.state.clone(),
+        };
+        let mut cells = vec![
+            node.role.clone(),
+            node.instance_id.clone(),
+            or_unknown(&node.public_ip),
+            state,
+        ];
+        if wide {
+            cells.extend([
+                or_unknown(&node.private_ip),
+                or_unknown(&node.instance_type),
+                or_unknown(&node.availability_zone),
+                or_unknown(&node.launch_time),
+                or_unknown(&node.role_tag),
+            ]);
+        }
+        if options.kubernetes {
+            match node.kubernetes.as_ref() {
+                Some(k8s) => cells.extend([
+                    if k8s.joined { "yes" } else { "no" }.to_owned(),
+                    k8s.ready.clone().unwrap_or_else(|| "-".to_owned()),
+                    k8s.kubelet_version
+                        .clone()
+                        .unwrap_or_else(|| "-".to_owned()),
+                    if k8s.roles.is_empty() {
+                        "-".to_owned()
+                    } else {
+                        k8s.roles.join(",")
+                    },
+                ]),
+                None => cells.extend(vec!["unknown".to_owned(); 4]),
+            }
+        }
+        table.add_row(Row::new(cells.iter().map(|c| Cell::new(c)).collect()));
     }
 
     table.print(&mut w)?;
@@ -0,0 +1,60 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+
+use crate::{
+    cli::report, config::Provider, operator::AwsOperator, ssh::SessionPool, usecase, Config,
+};
+
+pub async fn run(
+    config: Config,
+    to: String,
+    drain_timeout: Duration,
+    health_timeout: Duration,
+) -> anyhow::Result<()> {
+    let to = to.trim_start_matches('v').to_owned();
+    if to.split('.').count() != 2 || !to.split('.').all(|n| n.parse::<u32>().is_ok()) {
+        return Err(anyhow!("--to must be a minor version like 1.33, got {to}"));
+    }
+
+    match config.provider {
+        Provider::Aws => {
+            let operator = AwsOperator::from_config(&config).await?;
+            let node_config = &config.aws.as_ref().unwrap().ec2.node;
+            let sessions = Arc::new(SessionPool::new(node_config.ssh.clone()));
+            let mut cluster = config.cluster.clone();
+            cluster.kubernetes_version = to.clone();
+
+            let report = usecase::ec2::upgrade(
+                &cluster,
+                node_config.distribution,
+                &sessions,
+                &operator,
+                drain_timeout,
+                health_timeout,
+            )
+            .await?;
+
+            report::write_summary(std::io::stdout(), &report, "Result", |_| format!("v{to}"))?;
+
+            let failed = report.failed();
+            if failed > 0 {
+                return Err(anyhow!(
+                    "upgrade failed on {failed} of {} nodes",
+                    report.executions.len()
+                ));
+            }
+            if let Some(halted) = report.halted {
+                return Err(halted);
+            }
+            if config.cluster.kubernetes_version != to {
+                tracing::warn!(
+                    "update cluster.kubernetesVersion to {to} in the configuration \
+                     so that new nodes are provisioned with the same version"
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
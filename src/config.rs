@@ -4,8 +4,11 @@ use std::{
 };
 
 pub use aws::*;
-use error_stack::{Context, IntoReport, ResultExt};
+pub use cluster::{ClusterConfig, LoadBalancerConfig, LoadBalancerKind};
+use error_stack::{Context, IntoReport, Report, ResultExt};
 pub use provider::Provider;
+pub use proxy::ProxyConfig;
+pub use registry::RegistryConfig;
 use serde::Deserialize;
 
 use crate::operator::AwsTagSpec;
@@ -14,6 +17,12 @@ mod provider;
 
 mod aws;
 
+mod cluster;
+
+mod proxy;
+
+mod registry;
+
 #[derive(Debug)]
 pub struct ParseConfigError {}
 
@@ -36,6 +45,8 @@ pub struct Config {
     #[serde(deserialize_with = "provider::deserialize_provider")]
     pub provider: Provider,
     pub aws: Option<AwsConfig>,
+    #[serde(default)]
+    pub cluster: ClusterConfig,
 }
 
 impl Config {
@@ -47,10 +58,25 @@ impl Config {
             .change_context_lazy(ParseConfigError::new)
             .attach_printable_lazy(|| format!("Could not read file {path:?}"))?;
 
-        serde_yaml::from_reader::<_, Config>(&mut f)
+        let config = serde_yaml::from_reader::<_, Config>(&mut f)
             .report()
             .change_context_lazy(ParseConfigError::new)
-            .attach_printable_lazy(|| format!("Could not deserialize file {path:?}"))
+            .attach_printable_lazy(|| format!("Could not deserialize file {path:?}"))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> error_stack::Result<(), ParseConfigError> {
+        match self.provider {
+            // ARP and VRRP announcements do not move an address in a VPC.
+            Provider::Aws if self.cluster.load_balancer.is_some() => {
+                Err(Report::new(ParseConfigError::new()).attach_printable(
+                    "cluster.loadBalancer is not supported with the aws provider, \
+                     set cluster.controlPlaneEndpoint to an AWS Network Load Balancer instead",
+                ))
+            }
+            Provider::Aws => Ok(()),
+        }
     }
 
     pub fn aws_tag_spec(&self) -> Option<AwsTagSpec> {
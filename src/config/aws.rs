@@ -1,6 +1,16 @@
-use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Formatter},
+    path::PathBuf,
+};
 
-use crate::operator::AwsTag;
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    node::{Node as _, NodeRole, TagSelector, EC2},
+    operator::AwsTag,
+    provision::Distribution,
+};
 
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename = "aws")]
@@ -16,16 +26,28 @@ pub struct Ec2Config {
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Ec2NodeConfig {
-    pub distribution: String,
+    /// `auto` or omitted detects the distribution from `/etc/os-release` of each node.
+    #[serde(default, deserialize_with = "deserialize_distribution")]
+    pub distribution: Option<Distribution>,
     pub ssh: SshConfig,
     pub tag: TagConfig,
     pub master: Node,
     pub worker: Node,
+    /// Instance tag key to node label key. Tag values are copied to the labels.
+    #[serde(default)]
+    pub tag_labels: BTreeMap<String, String>,
+    /// Labels and taints of nodes matching a tag, applied on top of the role ones.
+    #[serde(default)]
+    pub overrides: Vec<NodeOverride>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct SshConfig {
     pub user: String,
+    pub identity_file: Option<PathBuf>,
+    /// Jump host in `[user@]host[:port]` form.
+    pub bastion: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -37,6 +59,114 @@ pub struct TagConfig {
 #[derive(Debug, Deserialize, Clone)]
 pub struct Node {
     pub tag: TagConfig,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub taints: Vec<Taint>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NodeOverride {
+    /// `key=value` matched against instance tags like `--selector`.
+    #[serde(deserialize_with = "deserialize_tag_selector")]
+    pub selector: TagSelector,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub taints: Vec<Taint>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Taint {
+    pub key: String,
+    pub value: Option<String>,
+    pub effect: TaintEffect,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum TaintEffect {
+    NoSchedule,
+    PreferNoSchedule,
+    NoExecute,
+}
+
+/// Labels and taints a node should have.
+#[derive(Debug, Default)]
+pub struct NodeLabels {
+    pub labels: BTreeMap<String, String>,
+    pub taints: Vec<Taint>,
+}
+
+impl NodeLabels {
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.taints.is_empty()
+    }
+
+    fn extend(&mut self, labels: &BTreeMap<String, String>, taints: &[Taint]) {
+        self.labels
+            .extend(labels.iter().map(|(k, v)| (k.clone(), v.clone())));
+        for taint in taints {
+            // a taint is identified by its key and effect.
+            self.taints
+                .retain(|t| t.key != taint.key || t.effect != taint.effect);
+            self.taints.push(taint.clone());
+        }
+    }
+}
+
+impl Ec2NodeConfig {
+    /// Labels and taints of the node. Role labels come first, then tag labels and matching overrides.
+    /// Later ones win for the same label key or taint key and effect.
+    pub fn node_labels(&self, role: NodeRole, node: &EC2) -> NodeLabels {
+        let role_config = match role {
+            NodeRole::Master => &self.master,
+            NodeRole::Worker => &self.worker,
+        };
+        let mut node_labels = NodeLabels::default();
+        node_labels.extend(&role_config.labels, &role_config.taints);
+
+        let tag_labels = self
+            .tag_labels
+            .iter()
+            .filter_map(|(tag_key, label_key)| {
+                let value = label_value(node.tag(tag_key)?);
+                Some((label_key.clone(), value))
+            })
+            .collect();
+        node_labels.extend(&tag_labels, &[]);
+
+        for matched in self.overrides.iter().filter(|o| o.selector.matches(node)) {
+            node_labels.extend(&matched.labels, &matched.taints);
+        }
+
+        node_labels
+    }
+}
+
+/// Replace characters not allowed in label values with `-` and fit the value in 63 characters.
+fn label_value(tag_value: &str) -> String {
+    let value = tag_value
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '-',
+        })
+        .take(63)
+        .collect::<String>();
+    // must begin and end with an alphanumeric character.
+    value
+        .trim_matches(|c: char| !c.is_ascii_alphanumeric())
+        .to_owned()
+}
+
+impl fmt::Display for Taint {
+    /// `key=value:Effect` as accepted by `kubectl taint`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.value.as_ref() {
+            Some(value) => write!(f, "{}={value}:{:?}", self.key, self.effect),
+            None => write!(f, "{}:{:?}", self.key, self.effect),
+        }
+    }
 }
 
 impl From<TagConfig> for AwsTag {
@@ -44,3 +174,46 @@ impl From<TagConfig> for AwsTag {
         AwsTag::key(c.key).value(c.value)
     }
 }
+
+fn deserialize_distribution<'de, D>(deserializer: D) -> Result<Option<Distribution>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    if s.eq_ignore_ascii_case("auto") {
+        return Ok(None);
+    }
+    s.parse::<Distribution>().map(Some).map_err(|err| {
+        serde::de::Error::invalid_value(serde::de::Unexpected::Str(&s), &err.as_str())
+    })
+}
+
+fn deserialize_tag_selector<'de, D>(deserializer: D) -> Result<TagSelector, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse::<TagSelector>().map_err(|err| {
+        serde::de::Error::invalid_value(serde::de::Unexpected::Str(&s), &err.as_str())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_value_replaces_invalid_characters() {
+        assert_eq!(label_value("web server #1"), "web-server--1");
+        assert_eq!(label_value("-team/a_b.c-"), "team-a_b.c");
+        assert_eq!(label_value("ü"), "");
+    }
+
+    #[test]
+    fn label_value_fits_63_characters() {
+        assert_eq!(label_value(&"a".repeat(70)), "a".repeat(63));
+        // trailing separators left by truncation are trimmed.
+        let value = format!("{}-b", "a".repeat(62));
+        assert_eq!(label_value(&value), "a".repeat(62));
+    }
+}
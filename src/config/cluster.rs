@@ -0,0 +1,132 @@
+use std::net::IpAddr;
+
+use serde::Deserialize;
+
+use crate::config::{ProxyConfig, RegistryConfig};
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterConfig {
+    #[serde(default = "default_name")]
+    pub name: String,
+    /// Kubernetes minor version such as `1.32`. Selects the package repository.
+    #[serde(default = "default_kubernetes_version")]
+    pub kubernetes_version: String,
+    #[serde(default = "default_pod_subnet")]
+    pub pod_subnet: String,
+    #[serde(default = "default_service_subnet")]
+    pub service_subnet: String,
+    /// Manifest path or url of the CNI plugin applied after `kubeadm init`.
+    /// The plugin must use `podSubnet` as its pod network.
+    #[serde(default = "default_cni_manifest")]
+    pub cni_manifest: String,
+    /// Stable api server address with optional port. Required for more than one master.
+    /// Defaults to the virtual ip of `loadBalancer`.
+    #[serde(default)]
+    pub control_plane_endpoint: Option<String>,
+    /// Load balancer deployed on masters in front of the api servers.
+    #[serde(default)]
+    pub load_balancer: Option<LoadBalancerConfig>,
+    /// Registry mirrors and credentials written to containerd `certs.d`.
+    #[serde(default)]
+    pub registries: Vec<RegistryConfig>,
+    /// HTTP proxy for nodes without direct internet access.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadBalancerConfig {
+    #[serde(rename = "type")]
+    pub kind: LoadBalancerKind,
+    /// Virtual ip announced by the master holding it.
+    pub vip: IpAddr,
+    /// Network interface of masters the virtual ip is attached to.
+    pub interface: String,
+    /// Port the virtual ip accepts api server traffic on.
+    /// Defaults to 6443 for kube-vip and 8443 for haproxy which runs next to the api server.
+    pub port: Option<u16>,
+    /// VRRP router id of keepalived, unique among load balancers sharing the network. Defaults to 51.
+    pub virtual_router_id: Option<u8>,
+    /// VRRP password of keepalived, up to 8 characters.
+    pub auth_pass: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoadBalancerKind {
+    /// kube-vip static pod in ARP mode.
+    KubeVip,
+    /// keepalived for the virtual ip and haproxy balancing to every master.
+    Haproxy,
+}
+
+impl LoadBalancerConfig {
+    pub fn port(&self) -> u16 {
+        match (self.port, self.kind) {
+            (Some(port), _) => port,
+            (None, LoadBalancerKind::KubeVip) => 6443,
+            (None, LoadBalancerKind::Haproxy) => 8443,
+        }
+    }
+
+    pub fn virtual_router_id(&self) -> u8 {
+        self.virtual_router_id.unwrap_or(51)
+    }
+
+    pub fn auth_pass(&self) -> &str {
+        self.auth_pass.as_deref().unwrap_or("kubeprov")
+    }
+}
+
+impl ClusterConfig {
+    /// Endpoint written to the kubeadm `controlPlaneEndpoint`.
+    pub fn control_plane_endpoint(&self) -> Option<String> {
+        self.control_plane_endpoint.clone().or_else(|| {
+            self.load_balancer.as_ref().map(|lb| match lb.vip {
+                IpAddr::V4(ip) => format!("{ip}:{}", lb.port()),
+                IpAddr::V6(ip) => format!("[{ip}]:{}", lb.port()),
+            })
+        })
+    }
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            name: default_name(),
+            kubernetes_version: default_kubernetes_version(),
+            pod_subnet: default_pod_subnet(),
+            service_subnet: default_service_subnet(),
+            cni_manifest: default_cni_manifest(),
+            control_plane_endpoint: None,
+            load_balancer: None,
+            registries: Vec::new(),
+            proxy: None,
+        }
+    }
+}
+
+// same as the kubeadm default cluster name.
+fn default_name() -> String {
+    "kubernetes".to_owned()
+}
+
+fn default_kubernetes_version() -> String {
+    "1.32".to_owned()
+}
+
+// flannel default network.
+fn default_pod_subnet() -> String {
+    "10.244.0.0/16".to_owned()
+}
+
+// same as the kubeadm default.
+fn default_service_subnet() -> String {
+    "10.96.0.0/12".to_owned()
+}
+
+fn default_cni_manifest() -> String {
+    "https://github.com/flannel-io/flannel/releases/latest/download/kube-flannel.yml".to_owned()
+}
@@ -0,0 +1,15 @@
+use serde::Deserialize;
+
+/// HTTP proxy used by package managers, containerd and kubelet on nodes.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyConfig {
+    /// Proxy url for http like `http://proxy.example.com:3128`.
+    pub http: Option<String>,
+    /// Proxy url for https. Usually the same as http.
+    pub https: Option<String>,
+    /// Hosts, domains and CIDRs reached directly in addition to the node private ips,
+    /// pod subnet, service subnet and control plane endpoint.
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+
+/// Container registry configured in containerd `certs.d`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryConfig {
+    /// Registry host like `docker.io` or `registry.internal:5000`.
+    /// `http://` prefix makes containerd talk plain http to the registry.
+    pub host: String,
+    /// Mirror urls tried in order before the registry itself.
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    /// Skip TLS verification of the registry and its mirrors.
+    #[serde(default)]
+    pub insecure: bool,
+    /// Credentials sent to the registry. Mirrors do not receive them.
+    pub auth: Option<RegistryAuth>,
+}
+
+/// Basic auth credentials. The password is read from an environment variable or a file
+/// so that it does not have to be written in the configuration.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryAuth {
+    pub username: String,
+    pub password_env: Option<String>,
+    pub password_file: Option<PathBuf>,
+}
+
+impl RegistryAuth {
+    pub fn password(&self) -> anyhow::Result<String> {
+        match (self.password_env.as_ref(), self.password_file.as_ref()) {
+            (Some(name), None) => {
+                std::env::var(name).with_context(|| format!("Could not read ${name}"))
+            }
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .map(|password| password.trim_end().to_owned())
+                .with_context(|| format!("Could not read {path:?}")),
+            _ => Err(anyhow!(
+                "exactly one of passwordEnv or passwordFile is required for {}",
+                self.username
+            )),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{provision::RemoteCommandExecutor, static_pods::run_script};
+
+/// etcd data dir of kubeadm, shared by the etcd container and the host.
+pub const ETCD_DATA_DIR: &str = "/var/lib/etcd";
+
+// etcdctl and etcdutl are not installed on the host. They are run in the etcd static pod container.
+// Print the path of the snapshot on the host.
+const SNAPSHOT_SAVE_SCRIPT: &str = r#"
+container=$($crictl ps --name '^etcd$' --state running --quiet | head -n 1)
+test -n "$container" || { echo "etcd container is not running" >&2; exit 1; }
+path=/var/lib/etcd/kubeprovision-snapshot-$(date -u +%Y%m%dT%H%M%SZ).db
+$crictl exec "$container" etcdctl \
+    --endpoints=https://127.0.0.1:2379 \
+    --cacert=/etc/kubernetes/pki/etcd/ca.crt \
+    --cert=/etc/kubernetes/pki/etcd/server.crt \
+    --key=/etc/kubernetes/pki/etcd/server.key \
+    snapshot save "$path" >&2
+echo "$path"
+"#;
+
+// Restore the snapshot given as $1 into a new data dir, stop the control plane static pods,
+// swap the data dirs and start the static pods again.
+// The previous data dir is kept and its path is printed. The snapshot is removed in any case.
+const SNAPSHOT_RESTORE_SCRIPT: &str = r#"
+restored=/var/lib/etcd/kubeprovision-restore
+flag() { sed -n "s/^ *- --$1=//p" "$manifests/etcd.yaml" | head -n 1; }
+moved=
+swapped=
+# put the previous data back if the swap did not complete.
+rollback() {
+    if [ -n "$moved" ] && [ -z "$swapped" ]; then
+        rm -rf /var/lib/etcd/member &&
+            mv "$previous/member" /var/lib/etcd/member &&
+            rmdir "$previous"
+    fi
+}
+# static pods are started again only on consistent data.
+trap 'rollback && start_static_pods; rm -rf "$restored" "$1"' EXIT
+
+name=$(flag name)
+peer_urls=$(flag initial-advertise-peer-urls)
+container=$($crictl ps --name '^etcd$' --state running --quiet | head -n 1)
+test -n "$container" || { echo "etcd container is not running" >&2; exit 1; }
+rm -rf "$restored"
+$crictl exec "$container" etcdutl snapshot restore "$1" \
+    --data-dir "$restored" \
+    --name "$name" \
+    --initial-cluster "$name=$peer_urls" \
+    --initial-advertise-peer-urls "$peer_urls" >&2
+
+stop_static_pods '^(etcd|kube-apiserver)$'
+previous=/var/lib/etcd-kubeprovision-$(date -u +%Y%m%dT%H%M%SZ)
+mkdir -p "$previous"
+mv /var/lib/etcd/member "$previous/"
+moved=1
+mv "$restored/member" /var/lib/etcd/member
+swapped=1
+echo "$previous"
+"#;
+
+/// Run etcd maintenance commands on a master of a kubeadm cluster.
+pub struct Etcd<Executor> {
+    executor: Executor,
+}
+
+impl<Executor> Etcd<Executor> {
+    pub fn new(executor: Executor) -> Self {
+        Self { executor }
+    }
+}
+
+impl<Executor> Etcd<Executor>
+where
+    Executor: RemoteCommandExecutor,
+{
+    /// Save a snapshot under `ETCD_DATA_DIR` and return its path.
+    pub async fn snapshot_save(&self) -> anyhow::Result<String> {
+        run_script(
+            &self.executor,
+            "etcd snapshot save",
+            SNAPSHOT_SAVE_SCRIPT,
+            &[],
+        )
+        .await
+    }
+
+    /// Replace the data of a single member etcd with the snapshot at path under `ETCD_DATA_DIR`.
+    /// Returns the previous data dir.
+    pub async fn snapshot_restore(&self, path: &str) -> anyhow::Result<String> {
+        run_script(
+            &self.executor,
+            "etcd snapshot restore",
+            SNAPSHOT_RESTORE_SCRIPT,
+            &[path],
+        )
+        .await
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+
+// Every kubeadm generated api server certificate has this SAN,
+// so it stays valid when the server address is rewritten to the public ip.
+const TLS_SERVER_NAME: &str = "kubernetes";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Kubeconfig {
+    #[serde(rename = "apiVersion")]
+    api_version: String,
+    kind: String,
+    #[serde(default)]
+    clusters: Vec<NamedCluster>,
+    #[serde(default)]
+    contexts: Vec<NamedContext>,
+    #[serde(default)]
+    users: Vec<NamedUser>,
+    #[serde(default)]
+    current_context: String,
+    #[serde(flatten)]
+    extra: Mapping,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NamedCluster {
+    name: String,
+    cluster: Cluster,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Cluster {
+    server: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tls_server_name: Option<String>,
+    #[serde(flatten)]
+    extra: Mapping,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NamedContext {
+    name: String,
+    context: KubeContext,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KubeContext {
+    cluster: String,
+    user: String,
+    #[serde(flatten)]
+    extra: Mapping,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NamedUser {
+    name: String,
+    user: Mapping,
+}
+
+impl Default for Kubeconfig {
+    fn default() -> Self {
+        Self {
+            api_version: "v1".to_owned(),
+            kind: "Config".to_owned(),
+            clusters: Vec::new(),
+            contexts: Vec::new(),
+            users: Vec::new(),
+            current_context: String::new(),
+            extra: Mapping::new(),
+        }
+    }
+}
+
+impl Kubeconfig {
+    pub fn from_slice(content: &[u8]) -> anyhow::Result<Self> {
+        serde_yaml::from_slice(content).context("Could not parse kubeconfig")
+    }
+
+    /// Load kubeconfig from path. Returns empty kubeconfig if the file does not exist.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read(path) {
+            Ok(content) => Self::from_slice(&content)
+                .with_context(|| format!("Could not load kubeconfig {path:?}")),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("Could not read kubeconfig {path:?}")),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = serde_yaml::to_vec(self)?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Could not write kubeconfig {path:?}"))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+
+        Ok(())
+    }
+
+    /// Rename admin.conf entries after the cluster and point the server at public_ip.
+    pub fn for_cluster(mut self, cluster_name: &str, public_ip: IpAddr) -> anyhow::Result<Self> {
+        let (mut cluster, mut context, mut user) =
+            match (self.clusters.pop(), self.contexts.pop(), self.users.pop()) {
+                (Some(cluster), Some(context), Some(user)) => (cluster, context, user),
+                _ => return Err(anyhow!("kubeconfig does not have cluster, context or user")),
+            };
+        let user_name = format!("{cluster_name}-admin");
+
+        cluster.name = cluster_name.to_owned();
+        cluster.cluster.server = rewrite_server(&cluster.cluster.server, public_ip);
+        cluster.cluster.tls_server_name = Some(TLS_SERVER_NAME.to_owned());
+        user.name = user_name.clone();
+        context.name = cluster_name.to_owned();
+        context.context.cluster = cluster_name.to_owned();
+        context.context.user = user_name;
+
+        Ok(Self {
+            clusters: vec![cluster],
+            contexts: vec![context],
+            users: vec![user],
+            current_context: cluster_name.to_owned(),
+            ..self
+        })
+    }
+
+    /// Merge other into self. Entries with the same name are replaced.
+    /// current context is taken from other only if self does not have one.
+    pub fn merge(&mut self, other: Kubeconfig) {
+        for cluster in other.clusters {
+            self.clusters.retain(|c| c.name != cluster.name);
+            self.clusters.push(cluster);
+        }
+        for context in other.contexts {
+            self.contexts.retain(|c| c.name != context.name);
+            self.contexts.push(context);
+        }
+        for user in other.users {
+            self.users.retain(|u| u.name != user.name);
+            self.users.push(user);
+        }
+        if self.current_context.is_empty() {
+            self.current_context = other.current_context;
+        }
+    }
+}
+
+/// Local kubeconfig path following kubectl convention.
+/// The first entry of `KUBECONFIG` is used, `~/.kube/config` otherwise.
+pub fn default_path() -> anyhow::Result<PathBuf> {
+    if let Some(path) = std::env::var_os("KUBECONFIG")
+        .and_then(|paths| std::env::split_paths(&paths).find(|p| !p.as_os_str().is_empty()))
+    {
+        return Ok(path);
+    }
+    let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set"))?;
+
+    Ok(PathBuf::from(home).join(".kube").join("config"))
+}
+
+// https://10.0.0.1:6443 -> https://{public_ip}:6443
+fn rewrite_server(server: &str, public_ip: IpAddr) -> String {
+    let (scheme, rest) = server.split_once("://").unwrap_or(("https", server));
+    let port = rest
+        .rsplit_once(':')
+        .map(|(_, port)| port)
+        .filter(|port| port.chars().all(|c| c.is_ascii_digit()))
+        .unwrap_or("6443");
+    let host = match public_ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{ip}]"),
+    };
+
+    format!("{scheme}://{host}:{port}")
+}
@@ -0,0 +1,365 @@
+use std::{collections::BTreeMap, net::IpAddr, process::Output, time::Duration};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::provision::{Command, RemoteCommandExecutor};
+
+/// kubeconfig written by `kubeadm init` on masters.
+pub const ADMIN_KUBECONFIG: &str = "/etc/kubernetes/admin.conf";
+
+/// kubeconfig bypassing RBAC written by `kubeadm init` since kubernetes 1.29.
+/// admin.conf is not authorized until kubeadm init binds its group.
+pub const SUPER_ADMIN_KUBECONFIG: &str = "/etc/kubernetes/super-admin.conf";
+
+/// kubeconfig written by `kubeadm init` or `kubeadm join` once the node is part of a cluster.
+pub const KUBELET_KUBECONFIG: &str = "/etc/kubernetes/kubelet.conf";
+
+const NODE_ROLE_LABEL_PREFIX: &str = "node-role.kubernetes.io/";
+
+/// Run kubectl on a master node with the admin kubeconfig.
+pub struct Kubectl<Executor> {
+    executor: Executor,
+}
+
+impl<Executor> Kubectl<Executor> {
+    pub fn new(executor: Executor) -> Self {
+        Self { executor }
+    }
+}
+
+impl<Executor> Kubectl<Executor>
+where
+    Executor: RemoteCommandExecutor,
+{
+    pub async fn nodes(&self) -> anyhow::Result<Vec<KubeNode>> {
+        let output = self.output(&["get", "nodes", "--output", "json"]).await?;
+        let list = serde_json::from_slice::<NodeList>(&output)
+            .context("Could not parse kubectl get nodes output")?;
+
+        Ok(list.items.into_iter().map(KubeNode::from).collect())
+    }
+
+    pub async fn pods(&self, namespace: &str) -> anyhow::Result<Vec<KubePod>> {
+        let output = self
+            .output(&["get", "pods", "--namespace", namespace, "--output", "json"])
+            .await?;
+        let list = serde_json::from_slice::<PodList>(&output)
+            .context("Could not parse kubectl get pods output")?;
+
+        Ok(list.items.into_iter().map(KubePod::from).collect())
+    }
+
+    /// Run a pod pinned to the node in the default namespace.
+    pub async fn run_pod(
+        &self,
+        name: &str,
+        node_name: &str,
+        image: &str,
+        label: &str,
+        command: &[&str],
+    ) -> anyhow::Result<()> {
+        let overrides = serde_json::json!({ "spec": { "nodeName": node_name } }).to_string();
+        let image = format!("--image={image}");
+        let label = format!("--labels={label}");
+        let overrides = format!("--overrides={overrides}");
+        let mut args = vec![
+            "run",
+            name,
+            &image,
+            &label,
+            &overrides,
+            "--restart=Never",
+            "--",
+        ];
+        args.extend_from_slice(command);
+
+        self.output(&args).await?;
+        Ok(())
+    }
+
+    /// Run command in the pod and return its output regardless of the exit status.
+    pub async fn exec(&self, pod: &str, command: &[&str]) -> anyhow::Result<Output> {
+        let mut args = vec!["exec", pod, "--"];
+        args.extend_from_slice(command);
+
+        Ok(self
+            .executor
+            .output(Command::Sudo(&kubectl_command(&args)))
+            .await?)
+    }
+
+    /// Delete pods matching the selector. Waits until they are gone if timeout is given.
+    pub async fn delete_pods(
+        &self,
+        selector: &str,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        let timeout = timeout.map(|timeout| format!("--timeout={}s", timeout.as_secs()));
+        let mut args = vec![
+            "delete",
+            "pods",
+            "--selector",
+            selector,
+            "--ignore-not-found",
+        ];
+        match timeout.as_deref() {
+            Some(timeout) => args.extend_from_slice(&["--wait=true", timeout]),
+            None => args.push("--wait=false"),
+        }
+        self.output(&args).await?;
+        Ok(())
+    }
+
+    /// Cordon the node and evict its pods.
+    /// DaemonSet pods are left as is and pods using emptyDir lose their data.
+    pub async fn drain(&self, node_name: &str, timeout: Duration) -> anyhow::Result<()> {
+        let timeout = format!("--timeout={}s", timeout.as_secs());
+        self.output(&[
+            "drain",
+            node_name,
+            "--ignore-daemonsets",
+            "--delete-emptydir-data",
+            &timeout,
+        ])
+        .await?;
+        Ok(())
+    }
+
+    /// Set labels on the node, overwriting existing values.
+    pub async fn label(
+        &self,
+        node_name: &str,
+        labels: &BTreeMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let labels = labels
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>();
+        let mut args = vec!["label", "node", node_name, "--overwrite"];
+        args.extend(labels.iter().map(String::as_str));
+
+        self.output(&args).await?;
+        Ok(())
+    }
+
+    /// Add taints in `key=value:Effect` form to the node, overwriting taints of the same key and effect.
+    pub async fn taint(&self, node_name: &str, taints: &[String]) -> anyhow::Result<()> {
+        let mut args = vec!["taint", "node", node_name, "--overwrite"];
+        args.extend(taints.iter().map(String::as_str));
+
+        self.output(&args).await?;
+        Ok(())
+    }
+
+    /// Set annotations in `key=value` form or remove them in `key-` form, overwriting existing values.
+    pub async fn annotate(&self, node_name: &str, annotations: &[String]) -> anyhow::Result<()> {
+        let mut args = vec!["annotate", "node", node_name, "--overwrite"];
+        args.extend(annotations.iter().map(String::as_str));
+
+        self.output(&args).await?;
+        Ok(())
+    }
+
+    pub async fn cordon(&self, node_name: &str) -> anyhow::Result<()> {
+        self.output(&["cordon", node_name]).await?;
+        Ok(())
+    }
+
+    pub async fn uncordon(&self, node_name: &str) -> anyhow::Result<()> {
+        self.output(&["uncordon", node_name]).await?;
+        Ok(())
+    }
+
+    pub async fn delete_node(&self, node_name: &str) -> anyhow::Result<()> {
+        self.output(&["delete", "node", node_name, "--ignore-not-found"])
+            .await?;
+        Ok(())
+    }
+
+    /// Run kubectl with args and return stdout.
+    async fn output(&self, args: &[&str]) -> anyhow::Result<Vec<u8>> {
+        let command = kubectl_command(args);
+        Ok(self
+            .executor
+            .stdout(Command::Sudo(&command), &command.join(" "))
+            .await?)
+    }
+}
+
+fn kubectl_command<'a>(args: &[&'a str]) -> Vec<&'a str> {
+    let mut command = vec!["kubectl", "--kubeconfig", ADMIN_KUBECONFIG];
+    command.extend_from_slice(args);
+    command
+}
+
+/// Kubernetes view of a node.
+#[derive(Debug, Clone)]
+pub struct KubeNode {
+    pub name: String,
+    pub internal_ip: Option<IpAddr>,
+    pub hostname: Option<String>,
+    /// Status of the Ready condition. True, False or Unknown.
+    pub ready: String,
+    pub kubelet_version: String,
+    pub roles: Vec<String>,
+    /// Cordoned, by drain or by hand.
+    pub unschedulable: bool,
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl KubeNode {
+    pub fn is_ready(&self) -> bool {
+        self.ready == "True"
+    }
+}
+
+/// Kubernetes view of a pod.
+#[derive(Debug, Clone)]
+pub struct KubePod {
+    pub name: String,
+    pub node_name: Option<String>,
+    /// Pending, Running, Succeeded, Failed or Unknown.
+    pub phase: String,
+    pub pod_ip: Option<IpAddr>,
+    pub ready: bool,
+}
+
+#[derive(Deserialize)]
+struct NodeList {
+    items: Vec<NodeObject>,
+}
+
+#[derive(Deserialize)]
+struct NodeObject {
+    metadata: ObjectMeta,
+    #[serde(default)]
+    spec: NodeSpec,
+    status: NodeStatus,
+}
+
+#[derive(Deserialize)]
+struct ObjectMeta {
+    name: String,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+struct NodeSpec {
+    #[serde(default)]
+    unschedulable: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NodeStatus {
+    #[serde(default)]
+    addresses: Vec<NodeAddress>,
+    #[serde(default)]
+    conditions: Vec<Condition>,
+    node_info: NodeInfo,
+}
+
+#[derive(Deserialize)]
+struct PodList {
+    items: Vec<PodObject>,
+}
+
+#[derive(Deserialize)]
+struct PodObject {
+    metadata: ObjectMeta,
+    #[serde(default)]
+    spec: PodSpec,
+    status: PodStatus,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct PodSpec {
+    node_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PodStatus {
+    #[serde(default)]
+    phase: String,
+    #[serde(rename = "podIP")]
+    pod_ip: Option<String>,
+    #[serde(default)]
+    conditions: Vec<Condition>,
+}
+
+#[derive(Deserialize)]
+struct NodeAddress {
+    #[serde(rename = "type")]
+    type_: String,
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct Condition {
+    #[serde(rename = "type")]
+    type_: String,
+    status: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NodeInfo {
+    kubelet_version: String,
+}
+
+impl From<NodeObject> for KubeNode {
+    fn from(node: NodeObject) -> Self {
+        let address = |type_: &str| {
+            node.status
+                .addresses
+                .iter()
+                .find(|a| a.type_ == type_)
+                .map(|a| a.address.clone())
+        };
+
+        KubeNode {
+            internal_ip: address("InternalIP").and_then(|ip| ip.parse().ok()),
+            hostname: address("Hostname"),
+            ready: node
+                .status
+                .conditions
+                .iter()
+                .find(|c| c.type_ == "Ready")
+                .map(|c| c.status.clone())
+                .unwrap_or_else(|| "Unknown".to_owned()),
+            kubelet_version: node.status.node_info.kubelet_version,
+            roles: node
+                .metadata
+                .labels
+                .keys()
+                .filter_map(|key| key.strip_prefix(NODE_ROLE_LABEL_PREFIX))
+                .map(str::to_owned)
+                .collect(),
+            unschedulable: node.spec.unschedulable,
+            annotations: node.metadata.annotations,
+            name: node.metadata.name,
+        }
+    }
+}
+
+impl From<PodObject> for KubePod {
+    fn from(pod: PodObject) -> Self {
+        KubePod {
+            name: pod.metadata.name,
+            node_name: pod.spec.node_name,
+            phase: pod.status.phase,
+            pod_ip: pod.status.pod_ip.and_then(|ip| ip.parse().ok()),
+            ready: pod
+                .status
+                .conditions
+                .iter()
+                .any(|c| c.type_ == "Ready" && c.status == "True"),
+        }
+    }
+}
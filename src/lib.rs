@@ -6,8 +6,16 @@ pub use cli::Cli;
 mod config;
 pub use config::Config;
 
+mod certs;
+mod etcd;
+mod kubeconfig;
+mod kubernetes;
 mod node;
 mod operator;
+mod preflight;
 mod provision;
+mod rollout;
 mod ssh;
+mod static_pods;
 mod usecase;
+mod verify;
@@ -1,8 +1,10 @@
 mod ec2;
+mod selector;
 
 use std::{fmt, fmt::Formatter, net::IpAddr, str::FromStr};
 
 pub use ec2::EC2;
+pub use selector::{NodeSelector, NodeTarget, TagSelector};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum NodeRole {
@@ -30,7 +32,7 @@ impl FromStr for NodeRole {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct NodeId(String);
 
 impl NodeId {
@@ -54,9 +56,11 @@ impl AsRef<str> for NodeId {
 pub trait Node {
     fn id(&self) -> &NodeId;
     fn public_ip(&self) -> Option<IpAddr>;
+    fn tag(&self, key: &str) -> Option<&str>;
+    fn availability_zone(&self) -> Option<&str>;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ClusterNodes<T> {
     pub master: Vec<T>,
     pub worker: Vec<T>,
@@ -80,6 +84,43 @@ impl<T: Node> ClusterNodes<T> {
 
         masters.chain(workers)
     }
+
+    /// Nodes matching the target. Target other than index may match several nodes.
+    pub fn find(&self, target: &NodeTarget) -> Vec<(NodeRole, &T)> {
+        self.indexed()
+            .filter(|(role, index, node)| target.matches(*role, *index, *node))
+            .map(|(role, _, node)| (role, node))
+            .collect()
+    }
+
+    /// Nodes with the index in their role.
+    pub fn indexed(&self) -> impl Iterator<Item = (NodeRole, usize, &T)> {
+        let masters = self.master.iter().map(|node| (NodeRole::Master, node));
+        let workers = self.worker.iter().map(|node| (NodeRole::Worker, node));
+
+        masters
+            .enumerate()
+            .chain(workers.enumerate())
+            .map(|(index, (role, node))| (role, index, node))
+    }
+
+    /// Keep nodes matching the selector.
+    /// Indices in the selector refer to the position before filtering.
+    pub fn select(self, selector: &NodeSelector) -> ClusterNodes<T> {
+        let keep = |role: NodeRole, nodes: Vec<T>| {
+            nodes
+                .into_iter()
+                .enumerate()
+                .filter(|(index, node)| selector.matches(role, *index, node))
+                .map(|(_, node)| node)
+                .collect()
+        };
+
+        ClusterNodes {
+            master: keep(NodeRole::Master, self.master),
+            worker: keep(NodeRole::Worker, self.worker),
+        }
+    }
 }
 
 impl<T> ClusterNodes<T> {
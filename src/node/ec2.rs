@@ -1,15 +1,21 @@
-use std::net::IpAddr;
+use std::{collections::HashMap, net::IpAddr};
 
 use anyhow::anyhow;
 use aws_sdk_ec2::model::InstanceStateName;
 
 use crate::node::{Node, NodeId};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EC2 {
     instance_id: NodeId,
     public_ip_address: Option<IpAddr>,
+    private_ip_address: Option<IpAddr>,
+    private_dns_name: Option<String>,
     state: InstanceStateName,
+    instance_type: Option<String>,
+    launch_time: Option<aws_smithy_types::DateTime>,
+    availability_zone: Option<String>,
+    tags: HashMap<String, String>,
 }
 
 impl Node for EC2 {
@@ -21,12 +27,37 @@ impl Node for EC2 {
     fn public_ip(&self) -> Option<IpAddr> {
         self.public_ip_address
     }
+    fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+    fn availability_zone(&self) -> Option<&str> {
+        self.availability_zone.as_deref()
+    }
 }
 
 impl EC2 {
     pub fn state(&self) -> InstanceStateName {
         self.state.clone()
     }
+
+    pub fn private_ip(&self) -> Option<IpAddr> {
+        self.private_ip_address
+    }
+
+    pub fn private_dns_name(&self) -> Option<&str> {
+        self.private_dns_name.as_deref()
+    }
+
+    pub fn instance_type(&self) -> Option<&str> {
+        self.instance_type.as_deref()
+    }
+
+    /// Launch time in RFC 3339 format.
+    pub fn launch_time(&self) -> Option<String> {
+        self.launch_time
+            .as_ref()
+            .and_then(|t| t.fmt(aws_smithy_types::date_time::Format::DateTime).ok())
+    }
 }
 
 impl TryFrom<aws_sdk_ec2::model::Instance> for EC2 {
@@ -42,15 +73,39 @@ impl TryFrom<aws_sdk_ec2::model::Instance> for EC2 {
             None => None,
         };
 
+        let private_ip_address = match instance.private_ip_address {
+            Some(ip) => Some(ip.parse()?),
+            None => None,
+        };
+
+        let private_dns_name = instance.private_dns_name.filter(|name| !name.is_empty());
+
         let state = instance
             .state
             .and_then(|s| s.name)
             .unwrap_or_else(|| InstanceStateName::Unknown("unknown".into()));
 
+        let instance_type = instance.instance_type.map(|t| t.as_str().to_owned());
+
+        let availability_zone = instance.placement.and_then(|p| p.availability_zone);
+
+        let tags = instance
+            .tags
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|tag| Some((tag.key?, tag.value.unwrap_or_default())))
+            .collect();
+
         Ok(Self {
             instance_id,
             public_ip_address,
+            private_ip_address,
+            private_dns_name,
             state,
+            instance_type,
+            launch_time: instance.launch_time,
+            availability_zone,
+            tags,
         })
     }
 }
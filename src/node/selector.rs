@@ -0,0 +1,177 @@
+use std::{fmt, fmt::Formatter, str::FromStr};
+
+use crate::node::{Node, NodeId, NodeRole};
+
+/// Node specified by instance id, role, role with index such as `worker-1`
+/// or role with index range such as `worker[0:2]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeTarget {
+    Id(NodeId),
+    Role(NodeRole),
+    Index(NodeRole, usize),
+    /// Start is inclusive and end is exclusive. Either bound can be omitted.
+    Range(NodeRole, Option<usize>, Option<usize>),
+}
+
+impl NodeTarget {
+    pub fn matches(&self, role: NodeRole, index: usize, node: &impl Node) -> bool {
+        match self {
+            NodeTarget::Id(id) => node.id() == id,
+            NodeTarget::Role(r) => *r == role,
+            NodeTarget::Index(r, i) => *r == role && *i == index,
+            NodeTarget::Range(r, start, end) => {
+                *r == role && start.unwrap_or(0) <= index && index < end.unwrap_or(usize::MAX)
+            }
+        }
+    }
+}
+
+impl FromStr for NodeTarget {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("i-") {
+            return Ok(NodeTarget::Id(NodeId::new(s)));
+        }
+        if let Some((role, range)) = s.strip_suffix(']').and_then(|s| s.split_once('[')) {
+            let (start, end) = range
+                .split_once(':')
+                .ok_or_else(|| format!("unexpected node range {range}. expected start:end"))?;
+            return Ok(NodeTarget::Range(
+                role.parse()?,
+                parse_bound(start)?,
+                parse_bound(end)?,
+            ));
+        }
+        match s.split_once('-') {
+            Some((role, index)) => {
+                let index = index
+                    .parse::<usize>()
+                    .map_err(|_| format!("unexpected node index {index}"))?;
+                Ok(NodeTarget::Index(role.parse()?, index))
+            }
+            None => Ok(NodeTarget::Role(s.parse()?)),
+        }
+    }
+}
+
+fn parse_bound(bound: &str) -> Result<Option<usize>, String> {
+    if bound.is_empty() {
+        return Ok(None);
+    }
+    bound
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("unexpected node index {bound}"))
+}
+
+impl fmt::Display for NodeTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let bound = |b: &Option<usize>| b.map(|b| b.to_string()).unwrap_or_default();
+        match self {
+            NodeTarget::Id(id) => write!(f, "{id}"),
+            NodeTarget::Role(role) => write!(f, "{role}"),
+            NodeTarget::Index(role, index) => write!(f, "{role}-{index}"),
+            NodeTarget::Range(role, start, end) => {
+                write!(f, "{role}[{}:{}]", bound(start), bound(end))
+            }
+        }
+    }
+}
+
+/// `key=value` matched against node tags.
+/// `az` is matched against the availability zone of the node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagSelector {
+    key: String,
+    value: String,
+}
+
+impl TagSelector {
+    pub fn matches(&self, node: &impl Node) -> bool {
+        let actual = match self.key.as_str() {
+            "az" => node.availability_zone(),
+            key => node.tag(key),
+        };
+        actual == Some(self.value.as_str())
+    }
+}
+
+impl FromStr for TagSelector {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok(TagSelector {
+                key: key.to_owned(),
+                value: value.to_owned(),
+            }),
+            _ => Err(format!("unexpected selector {s}. expected key=value")),
+        }
+    }
+}
+
+/// Conditions to narrow down target nodes. Empty selector selects all nodes.
+#[derive(Debug, Clone, Default)]
+pub struct NodeSelector {
+    pub role: Option<NodeRole>,
+    /// Node must match any of them if not empty.
+    pub include: Vec<NodeTarget>,
+    /// Node must not match any of them.
+    pub exclude: Vec<NodeTarget>,
+    /// Node must match all of them.
+    pub tags: Vec<TagSelector>,
+}
+
+impl NodeSelector {
+    pub fn matches(&self, role: NodeRole, index: usize, node: &impl Node) -> bool {
+        (self.role.is_none() || self.role == Some(role))
+            && (self.include.is_empty()
+                || self.include.iter().any(|t| t.matches(role, index, node)))
+            && !self.exclude.iter().any(|t| t.matches(role, index, node))
+            && self.tags.iter().all(|t| t.matches(node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_target_from_str() {
+        assert_eq!(
+            "i-0123456789abcdef0".parse(),
+            Ok(NodeTarget::Id(NodeId::new("i-0123456789abcdef0")))
+        );
+        assert_eq!("master".parse(), Ok(NodeTarget::Role(NodeRole::Master)));
+        assert_eq!(
+            "worker-1".parse(),
+            Ok(NodeTarget::Index(NodeRole::Worker, 1))
+        );
+        assert_eq!(
+            "worker[0:2]".parse(),
+            Ok(NodeTarget::Range(NodeRole::Worker, Some(0), Some(2)))
+        );
+        assert_eq!(
+            "Master[1:]".parse(),
+            Ok(NodeTarget::Range(NodeRole::Master, Some(1), None))
+        );
+        assert_eq!(
+            "worker[:3]".parse(),
+            Ok(NodeTarget::Range(NodeRole::Worker, None, Some(3)))
+        );
+    }
+
+    #[test]
+    fn node_target_from_str_errors() {
+        assert!("worker-x".parse::<NodeTarget>().is_err());
+        assert!("worker[1]".parse::<NodeTarget>().is_err());
+        assert!("worker[a:2]".parse::<NodeTarget>().is_err());
+        assert!("node-1".parse::<NodeTarget>().is_err());
+    }
+
+    #[test]
+    fn node_target_display_round_trip() {
+        for target in ["master", "worker-1", "worker[0:2]", "worker[:3]", "i-0abc"] {
+            assert_eq!(target.parse::<NodeTarget>().unwrap().to_string(), target);
+        }
+    }
+}
@@ -7,7 +7,7 @@ use aws_sdk_ec2::{
 };
 
 use crate::{
-    node::{ClusterNodes, NodeRole, EC2},
+    node::{ClusterNodes, Node, NodeRole, EC2},
     Config,
 };
 
@@ -112,6 +112,10 @@ impl AwsOperator {
             }
         }
 
+        // keep the order stable so that nodes can be referred by index such as worker-1.
+        master_nodes.sort_by(|a, b| a.id().cmp(b.id()));
+        worker_nodes.sort_by(|a, b| a.id().cmp(b.id()));
+
         Ok(ClusterNodes {
             master: master_nodes,
             worker: worker_nodes,
@@ -0,0 +1,459 @@
+use std::{collections::HashMap, fmt, fmt::Formatter};
+
+use anyhow::anyhow;
+
+use crate::{
+    node::{NodeId, NodeRole},
+    provision::{Command, RemoteCommandExecutor},
+};
+
+/// Tested versions of supported distributions. 9 matches rhel 9.3 as well.
+const SUPPORTED_DISTRIBUTIONS: &[(&str, &[&str])] = &[
+    ("ubuntu", &["20.04", "22.04", "24.04"]),
+    ("debian", &["11", "12"]),
+    ("rhel", &["8", "9"]),
+    ("rocky", &["8", "9"]),
+    ("amzn", &["2", "2023"]),
+];
+
+/// Package repositories which must be reachable from every node.
+/// Mirrors configured in the node's apt sources or yum repositories are checked as well.
+const PACKAGE_REPOSITORIES: &[&str] = &["https://pkgs.k8s.io/"];
+
+const MASTER_MIN_CPUS: u32 = 2;
+// Same threshold as kubeadm. MemTotal of a 2GB instance is slightly below 2GB
+// because of memory reserved by the kernel.
+const MASTER_MIN_MEMORY_MB: u64 = 1700;
+
+// https://kubernetes.io/docs/reference/ports-and-protocols/
+const MASTER_PORTS: &[u16] = &[6443, 2379, 2380, 10250, 10257, 10259];
+const WORKER_PORTS: &[u16] = &[10250];
+
+// Print node facts as key=value lines. Arguments are urls to check connectivity.
+const FACTS_SCRIPT: &str = r#"
+. /etc/os-release
+echo "os_id=$ID"
+echo "os_version=$VERSION_ID"
+echo "cpus=$(nproc)"
+echo "mem_kb=$(awk '/^MemTotal:/ {print $2}' /proc/meminfo)"
+echo "swap_kb=$(awk '/^SwapTotal:/ {print $2}' /proc/meminfo)"
+echo "hostname=$(hostname)"
+echo "product_uuid=$(cat /sys/class/dmi/id/product_uuid)"
+echo "macs=$(cat /sys/class/net/*/address | grep -v '^00:00:00:00:00:00$' | tr '\n' ' ')"
+test -f /etc/kubernetes/kubelet.conf && echo "joined=true"
+echo "cgroup_fs=$(stat -fc %T /sys/fs/cgroup)"
+echo "listening=$(ss -Htln | awk '{n = split($4, a, ":"); print a[n]}' | sort -un | tr '\n' ' ')"
+mirrors=$(
+    cat /etc/apt/sources.list /etc/apt/sources.list.d/*.list 2>/dev/null \
+        | awk '$1 == "deb" { for (i = 2; i <= NF; i++) if ($i ~ /^https?:/) { print $i; break } }'
+    cat /etc/apt/sources.list.d/*.sources 2>/dev/null \
+        | awk '$1 == "URIs:" { for (i = 2; i <= NF; i++) if ($i ~ /^https?:/) print $i }'
+    # yum urls contain variables like $basearch, so only their hosts are checked.
+    cat /etc/yum.repos.d/*.repo 2>/dev/null \
+        | sed -n 's/^ *\(baseurl\|mirrorlist\|metalink\) *= *\(https\?:\/\/[^/]*\).*/\2\//p' \
+        | while read -r url; do
+            for var in /etc/dnf/vars/* /etc/yum/vars/*; do
+                test -f "$var" && url=${url//\$$(basename "$var")/$(cat "$var")}
+            done
+            case $url in *'$'*) ;; *) echo "$url" ;; esac
+        done
+)
+mirrors=$(echo "$mirrors" | sort -u)
+for url in "$@" $mirrors; do
+    if curl -sS -o /dev/null --max-time 10 "$url" 2>/dev/null; then
+        echo "reachable=$url"
+    else
+        echo "unreachable=$url"
+    fi
+done
+"#;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "pass"),
+            CheckStatus::Warn => write!(f, "warn"),
+            CheckStatus::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    fn pass_if(name: &'static str, ok: bool, detail: impl Into<String>) -> Self {
+        let status = if ok {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Fail
+        };
+        Self::new(name, status, detail)
+    }
+}
+
+/// Facts of a node which the checks are evaluated against.
+#[derive(Debug, Default)]
+pub struct NodeFacts {
+    os_id: String,
+    os_version: String,
+    cpus: u32,
+    mem_kb: u64,
+    swap_kb: u64,
+    hostname: String,
+    product_uuid: String,
+    macs: Vec<String>,
+    cgroup_fs: String,
+    listening: Vec<u16>,
+    /// kubelet is already configured by kubeadm.
+    joined: bool,
+    reachable: Vec<String>,
+    unreachable: Vec<String>,
+}
+
+impl NodeFacts {
+    pub async fn gather(executor: &impl RemoteCommandExecutor) -> anyhow::Result<Self> {
+        let mut command = vec!["bash", "-c", FACTS_SCRIPT, "kubeprovision"];
+        command.extend_from_slice(PACKAGE_REPOSITORIES);
+
+        // product_uuid is readable only by root.
+        let output = executor.output(Command::Sudo(&command)).await?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "could not gather node facts: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(Self::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    fn parse(output: &str) -> Self {
+        let words = |value: &str| value.split_whitespace().map(str::to_owned).collect();
+        let mut facts = NodeFacts::default();
+
+        for (key, value) in output.lines().filter_map(|line| line.split_once('=')) {
+            let value = value.trim();
+            match key {
+                "os_id" => facts.os_id = value.to_owned(),
+                "os_version" => facts.os_version = value.to_owned(),
+                "cpus" => facts.cpus = value.parse().unwrap_or_default(),
+                "mem_kb" => facts.mem_kb = value.parse().unwrap_or_default(),
+                "swap_kb" => facts.swap_kb = value.parse().unwrap_or_default(),
+                "hostname" => facts.hostname = value.to_owned(),
+                "product_uuid" => facts.product_uuid = value.to_lowercase(),
+                "macs" => facts.macs = words(value),
+                "cgroup_fs" => facts.cgroup_fs = value.to_owned(),
+                "listening" => {
+                    facts.listening = value
+                        .split_whitespace()
+                        .filter_map(|port| port.parse::<u16>().ok())
+                        .collect()
+                }
+                "joined" => facts.joined = value == "true",
+                "reachable" => facts.reachable.push(value.to_owned()),
+                "unreachable" => facts.unreachable.push(value.to_owned()),
+                _ => {}
+            }
+        }
+
+        facts
+    }
+}
+
+/// Checks of a node.
+pub struct NodePreflight {
+    pub role: NodeRole,
+    pub node_id: NodeId,
+    pub checks: Vec<Check>,
+}
+
+impl NodePreflight {
+    pub fn failed(&self) -> bool {
+        self.checks.iter().any(|c| c.status == CheckStatus::Fail)
+    }
+}
+
+/// Evaluate checks for each node. Nodes whose facts could not be gathered fail the connect check.
+pub fn evaluate(nodes: Vec<(NodeRole, NodeId, anyhow::Result<NodeFacts>)>) -> Vec<NodePreflight> {
+    let facts = nodes
+        .iter()
+        .filter_map(|(_, id, facts)| facts.as_ref().ok().map(|facts| (id, facts)))
+        .collect::<Vec<_>>();
+    let mut duplicates = duplicate_checks(&facts);
+
+    nodes
+        .iter()
+        .map(|(role, node_id, facts)| {
+            let checks = match facts {
+                Ok(facts) => {
+                    let mut checks = node_checks(*role, facts);
+                    checks.extend(duplicates.remove(node_id).unwrap_or_default());
+                    checks
+                }
+                Err(err) => vec![Check::new("connect", CheckStatus::Fail, format!("{err:#}"))],
+            };
+            NodePreflight {
+                role: *role,
+                node_id: node_id.clone(),
+                checks,
+            }
+        })
+        .collect()
+}
+
+fn node_checks(role: NodeRole, facts: &NodeFacts) -> Vec<Check> {
+    let mut checks = vec![distribution_check(facts)];
+
+    if role == NodeRole::Master {
+        checks.push(Check::pass_if(
+            "cpu",
+            facts.cpus >= MASTER_MIN_CPUS,
+            format!("{} cpus, {MASTER_MIN_CPUS} required", facts.cpus),
+        ));
+        let mem_mb = facts.mem_kb / 1024;
+        checks.push(Check::pass_if(
+            "memory",
+            mem_mb >= MASTER_MIN_MEMORY_MB,
+            format!("{mem_mb}MB, {MASTER_MIN_MEMORY_MB}MB required"),
+        ));
+    }
+
+    let ports = match role {
+        NodeRole::Master => MASTER_PORTS,
+        NodeRole::Worker => WORKER_PORTS,
+    };
+    let in_use = ports
+        .iter()
+        .filter(|port| facts.listening.contains(port))
+        .map(u16::to_string)
+        .collect::<Vec<_>>();
+    checks.push(if in_use.is_empty() {
+        Check::new("ports", CheckStatus::Pass, "required ports are free")
+    } else if facts.joined {
+        // kubernetes components of the node itself listen on them.
+        Check::new(
+            "ports",
+            CheckStatus::Warn,
+            format!(
+                "in use: {}. node already joined a cluster",
+                in_use.join(",")
+            ),
+        )
+    } else {
+        Check::new(
+            "ports",
+            CheckStatus::Fail,
+            format!("in use: {}", in_use.join(",")),
+        )
+    });
+
+    checks.push(if facts.cgroup_fs == "cgroup2fs" {
+        Check::new("cgroup", CheckStatus::Pass, "v2")
+    } else {
+        Check::new(
+            "cgroup",
+            CheckStatus::Warn,
+            format!("v1 ({}). cgroup v2 is recommended", facts.cgroup_fs),
+        )
+    });
+
+    // provision turns swap off, so enabled swap is not fatal.
+    checks.push(if facts.swap_kb == 0 {
+        Check::new("swap", CheckStatus::Pass, "disabled")
+    } else {
+        Check::new(
+            "swap",
+            CheckStatus::Warn,
+            format!("{}MB enabled. provision disables it", facts.swap_kb / 1024),
+        )
+    });
+
+    checks.push(if facts.unreachable.is_empty() {
+        Check::new(
+            "connectivity",
+            CheckStatus::Pass,
+            format!("{} mirrors reachable", facts.reachable.len()),
+        )
+    } else {
+        Check::new(
+            "connectivity",
+            CheckStatus::Fail,
+            format!("unreachable: {}", facts.unreachable.join(" ")),
+        )
+    });
+
+    checks
+}
+
+fn distribution_check(facts: &NodeFacts) -> Check {
+    let distribution = format!("{} {}", facts.os_id, facts.os_version);
+
+    match SUPPORTED_DISTRIBUTIONS
+        .iter()
+        .find(|(id, _)| *id == facts.os_id)
+    {
+        Some((_, versions))
+            if versions.iter().any(|version| {
+                facts.os_version == *version || facts.os_version.starts_with(&format!("{version}."))
+            }) =>
+        {
+            Check::new("distribution", CheckStatus::Pass, distribution)
+        }
+        Some(_) => Check::new(
+            "distribution",
+            CheckStatus::Warn,
+            format!("{distribution} is not tested"),
+        ),
+        None => Check::new(
+            "distribution",
+            CheckStatus::Fail,
+            format!("{distribution} is not supported"),
+        ),
+    }
+}
+
+/// hostname, MAC addresses and product_uuid must be unique in the cluster.
+fn duplicate_checks(facts: &[(&NodeId, &NodeFacts)]) -> HashMap<NodeId, Vec<Check>> {
+    let mut owners = HashMap::<(&'static str, &str), Vec<&NodeId>>::new();
+    for (node_id, facts) in facts {
+        owners
+            .entry(("hostname", facts.hostname.as_str()))
+            .or_default()
+            .push(node_id);
+        owners
+            .entry(("product_uuid", facts.product_uuid.as_str()))
+            .or_default()
+            .push(node_id);
+        for mac in facts.macs.iter() {
+            owners
+                .entry(("mac", mac.as_str()))
+                .or_default()
+                .push(node_id);
+        }
+    }
+
+    let mut checks = HashMap::<NodeId, Vec<Check>>::new();
+    for (node_id, facts) in facts {
+        let macs = facts.macs.iter().map(String::as_str).collect::<Vec<_>>();
+        let values = [
+            ("hostname", vec![facts.hostname.as_str()]),
+            ("mac", macs),
+            ("product_uuid", vec![facts.product_uuid.as_str()]),
+        ];
+
+        for (name, values) in values {
+            let shared = values
+                .iter()
+                .filter_map(|value| {
+                    let others = owners[&(name, *value)]
+                        .iter()
+                        .filter(|owner| **owner != *node_id)
+                        .map(|owner| owner.to_string())
+                        .collect::<Vec<_>>();
+                    (!others.is_empty())
+                        .then(|| format!("{value} is also used by {}", others.join(",")))
+                })
+                .collect::<Vec<_>>();
+            let check = if shared.is_empty() {
+                Check::new(name, CheckStatus::Pass, values.join(" "))
+            } else {
+                Check::new(name, CheckStatus::Fail, shared.join(", "))
+            };
+            checks.entry((*node_id).clone()).or_default().push(check);
+        }
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts(hostname: &str, mac: &str, product_uuid: &str) -> NodeFacts {
+        NodeFacts::parse(&format!(
+            "hostname={hostname}\nmacs={mac} \nproduct_uuid={product_uuid}\n"
+        ))
+    }
+
+    fn check<'a>(result: &'a NodePreflight, name: &str) -> &'a Check {
+        result
+            .checks
+            .iter()
+            .find(|check| check.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_facts() {
+        let facts = NodeFacts::parse(
+            "os_id=ubuntu\nos_version=24.04\ncpus=2\nmem_kb=3951144\nswap_kb=0\n\
+             hostname=ip-10-0-1-10\nproduct_uuid=EC2A1B2C-0000\n\
+             macs=0a:1b:2c:3d:4e:5f 0a:1b:2c:3d:4e:60 \njoined=true\ncgroup_fs=cgroup2fs\n\
+             listening=22 53 10250 \nreachable=https://pkgs.k8s.io/\n\
+             unreachable=http://archive.ubuntu.com/ubuntu/\nunrelated line\n",
+        );
+        assert_eq!(facts.os_id, "ubuntu");
+        assert_eq!(facts.os_version, "24.04");
+        assert_eq!(facts.cpus, 2);
+        assert_eq!(facts.mem_kb, 3951144);
+        assert_eq!(facts.hostname, "ip-10-0-1-10");
+        assert_eq!(facts.product_uuid, "ec2a1b2c-0000");
+        assert_eq!(facts.macs, ["0a:1b:2c:3d:4e:5f", "0a:1b:2c:3d:4e:60"]);
+        assert!(facts.joined);
+        assert_eq!(facts.cgroup_fs, "cgroup2fs");
+        assert_eq!(facts.listening, [22, 53, 10250]);
+        assert_eq!(facts.reachable, ["https://pkgs.k8s.io/"]);
+        assert_eq!(facts.unreachable, ["http://archive.ubuntu.com/ubuntu/"]);
+    }
+
+    #[test]
+    fn evaluate_duplicates_across_nodes() {
+        let results = evaluate(vec![
+            (
+                NodeRole::Master,
+                NodeId::new("i-1"),
+                Ok(facts("ip-10-0-0-1", "0a:00:00:00:00:01", "uuid-1")),
+            ),
+            (
+                NodeRole::Worker,
+                NodeId::new("i-2"),
+                Ok(facts("ip-10-0-0-1", "0a:00:00:00:00:02", "UUID-1")),
+            ),
+            (
+                NodeRole::Worker,
+                NodeId::new("i-3"),
+                Err(anyhow!("connection refused")),
+            ),
+        ]);
+
+        assert_eq!(check(&results[0], "hostname").status, CheckStatus::Fail);
+        assert_eq!(
+            check(&results[0], "hostname").detail,
+            "ip-10-0-0-1 is also used by i-2"
+        );
+        assert_eq!(check(&results[1], "product_uuid").status, CheckStatus::Fail);
+        assert_eq!(check(&results[1], "mac").status, CheckStatus::Pass);
+        assert_eq!(check(&results[2], "connect").status, CheckStatus::Fail);
+    }
+}
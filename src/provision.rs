@@ -1,5 +1,19 @@
+mod distribution;
+pub use distribution::Distribution;
+
+mod load_balancer;
+
 mod provisioner;
-pub use provisioner::Provisioner;
+pub use provisioner::{JoinToken, Provisioner};
+
+mod proxy;
+pub use proxy::ProxyEnv;
+
+mod registry;
+pub use registry::RegistryHosts;
 
 mod remote_command;
 pub use remote_command::{Command, RemoteCommandExecutor};
+
+mod template;
+pub use template::Vars;
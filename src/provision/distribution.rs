@@ -0,0 +1,221 @@
+use std::{fmt, fmt::Formatter, str::FromStr};
+
+/// Linux distribution of a node. Decides the package manager and repositories used by provision.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    Ubuntu,
+    Debian,
+    Rhel,
+    Rocky,
+    AmazonLinux,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PackageManager {
+    Apt,
+    Dnf,
+    Yum,
+}
+
+/// Package repository written to the node before installing packages.
+pub struct Repository {
+    pub path: &'static str,
+    pub template: &'static str,
+    /// Signing key url and keyring path. Only apt repositories need the key on disk.
+    pub key: Option<(&'static str, &'static str)>,
+}
+
+// https://kubernetes.io/docs/setup/production-environment/tools/kubeadm/install-kubeadm/
+const KUBERNETES_APT_KEYRING: &str = "/etc/apt/keyrings/kubernetes-apt-keyring.gpg";
+const KUBERNETES_APT_KEY: &str =
+    "https://pkgs.k8s.io/core:/stable:/v{{ kubernetes.version }}/deb/Release.key";
+const KUBERNETES_APT_LIST: &str = "\
+deb [signed-by=/etc/apt/keyrings/kubernetes-apt-keyring.gpg] https://pkgs.k8s.io/core:/stable:/v{{ kubernetes.version }}/deb/ /
+";
+
+const KUBERNETES_YUM_REPO: &str = "\
+[kubernetes]
+name=Kubernetes
+baseurl=https://pkgs.k8s.io/core:/stable:/v{{ kubernetes.version }}/rpm/
+enabled=1
+gpgcheck=1
+gpgkey=https://pkgs.k8s.io/core:/stable:/v{{ kubernetes.version }}/rpm/repodata/repomd.xml.key
+exclude=kubelet kubeadm kubectl cri-tools kubernetes-cni
+";
+
+// RHEL and Rocky do not ship containerd. It is installed from the docker repository.
+const DOCKER_YUM_REPO: &str = "\
+[docker-ce-stable]
+name=Docker CE Stable
+baseurl=https://download.docker.com/linux/centos/$releasever/$basearch/stable
+enabled=1
+gpgcheck=1
+gpgkey=https://download.docker.com/linux/centos/gpg
+";
+
+/// Packages pinned so that unattended upgrades do not bump the cluster version.
+pub const KUBERNETES_PACKAGES: &[&str] = &["kubelet", "kubeadm", "kubectl"];
+
+impl Distribution {
+    /// Detect distribution from the content of `/etc/os-release`.
+    /// `ID_LIKE` is consulted when `ID` is not known, e.g. almalinux is treated as rhel.
+    pub fn from_os_release(content: &str) -> Option<Self> {
+        let field = |key: &str| {
+            content.lines().find_map(|line| {
+                line.strip_prefix(key)
+                    .and_then(|rest| rest.strip_prefix('='))
+                    .map(|value| value.trim().trim_matches('"').to_owned())
+            })
+        };
+
+        let id = field("ID")?;
+        id.parse().ok().or_else(|| {
+            field("ID_LIKE")?
+                .split_whitespace()
+                .find_map(|like| like.parse().ok())
+        })
+    }
+
+    pub fn package_manager(&self) -> PackageManager {
+        match self {
+            Distribution::Ubuntu | Distribution::Debian => PackageManager::Apt,
+            Distribution::Rhel | Distribution::Rocky => PackageManager::Dnf,
+            Distribution::AmazonLinux => PackageManager::Yum,
+        }
+    }
+
+    pub fn containerd_package(&self) -> &'static str {
+        match self {
+            Distribution::Rhel | Distribution::Rocky => "containerd.io",
+            _ => "containerd",
+        }
+    }
+
+    /// Repositories required for containerd and kubernetes packages.
+    pub fn repositories(&self) -> Vec<Repository> {
+        match self {
+            Distribution::Rhel | Distribution::Rocky => vec![
+                Repository {
+                    path: "/etc/yum.repos.d/docker-ce.repo",
+                    template: DOCKER_YUM_REPO,
+                    key: None,
+                },
+                self.kubernetes_repository(),
+            ],
+            _ => vec![self.kubernetes_repository()],
+        }
+    }
+
+    /// Repository of `kubernetes.version`.
+    pub fn kubernetes_repository(&self) -> Repository {
+        match self.package_manager() {
+            PackageManager::Apt => Repository {
+                path: "/etc/apt/sources.list.d/kubernetes.list",
+                template: KUBERNETES_APT_LIST,
+                key: Some((KUBERNETES_APT_KEY, KUBERNETES_APT_KEYRING)),
+            },
+            PackageManager::Dnf | PackageManager::Yum => Repository {
+                path: "/etc/yum.repos.d/kubernetes.repo",
+                template: KUBERNETES_YUM_REPO,
+                key: None,
+            },
+        }
+    }
+
+    /// SELinux must be permissive for containers to access the host filesystem.
+    pub fn has_selinux(&self) -> bool {
+        matches!(self, Distribution::Rhel | Distribution::Rocky)
+    }
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_ref() {
+            "ubuntu" => Ok(Distribution::Ubuntu),
+            "debian" => Ok(Distribution::Debian),
+            "rhel" => Ok(Distribution::Rhel),
+            "rocky" => Ok(Distribution::Rocky),
+            "amzn" | "amazonlinux" => Ok(Distribution::AmazonLinux),
+            _ => Err(format!("unsupported distribution: {s}")),
+        }
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Distribution::Ubuntu => write!(f, "ubuntu"),
+            Distribution::Debian => write!(f, "debian"),
+            Distribution::Rhel => write!(f, "rhel"),
+            Distribution::Rocky => write!(f, "rocky"),
+            Distribution::AmazonLinux => write!(f, "amzn"),
+        }
+    }
+}
+
+impl PackageManager {
+    /// Packages needed to add repositories.
+    pub fn prerequisites(&self) -> &'static [&'static str] {
+        match self {
+            PackageManager::Apt => &["apt-transport-https", "ca-certificates", "curl", "gpg"],
+            PackageManager::Dnf | PackageManager::Yum => &[],
+        }
+    }
+
+    /// Command refreshing the package index. None if install refreshes it anyway.
+    pub fn update(&self) -> Option<&'static [&'static str]> {
+        match self {
+            PackageManager::Apt => Some(&["apt-get", "update"]),
+            PackageManager::Dnf | PackageManager::Yum => None,
+        }
+    }
+
+    pub fn install<'a>(&self, packages: &[&'a str]) -> Vec<&'a str> {
+        let mut command = match self {
+            PackageManager::Apt => vec!["apt-get", "install", "--yes"],
+            PackageManager::Dnf => vec!["dnf", "install", "--assumeyes"],
+            PackageManager::Yum => vec!["yum", "install", "--assumeyes"],
+        };
+        command.extend_from_slice(packages);
+        command
+    }
+
+    /// Install or upgrade `KUBERNETES_PACKAGES`, which are held or excluded from the repository.
+    pub fn install_held<'a>(&self, packages: &[&'a str]) -> Vec<&'a str> {
+        let mut command = self.install(&[]);
+        match self {
+            // held packages are upgraded only when they are named explicitly like here.
+            PackageManager::Apt => command.push("--allow-change-held-packages"),
+            PackageManager::Dnf | PackageManager::Yum => {
+                command.push("--disableexcludes=kubernetes")
+            }
+        }
+        command.extend_from_slice(packages);
+        command
+    }
+
+    pub fn remove<'a>(&self, packages: &[&'a str]) -> Vec<&'a str> {
+        let mut command = match self {
+            PackageManager::Apt => {
+                vec!["apt-get", "purge", "--yes", "--allow-change-held-packages"]
+            }
+            PackageManager::Dnf => vec!["dnf", "remove", "--assumeyes"],
+            PackageManager::Yum => vec!["yum", "remove", "--assumeyes"],
+        };
+        command.extend_from_slice(packages);
+        command
+    }
+
+    /// Command preventing the packages from being upgraded. None if the repository excludes them.
+    pub fn hold<'a>(&self, packages: &[&'a str]) -> Option<Vec<&'a str>> {
+        match self {
+            PackageManager::Apt => {
+                let mut command = vec!["apt-mark", "hold"];
+                command.extend_from_slice(packages);
+                Some(command)
+            }
+            PackageManager::Dnf | PackageManager::Yum => None,
+        }
+    }
+}
@@ -0,0 +1,112 @@
+// https://kube-vip.io/docs/installation/static/
+pub(super) const KUBE_VIP_MANIFEST_PATH: &str = "/etc/kubernetes/manifests/kube-vip.yaml";
+
+pub(super) const KUBE_VIP_MANIFEST: &str = "\
+apiVersion: v1
+kind: Pod
+metadata:
+  name: kube-vip
+  namespace: kube-system
+spec:
+  containers:
+  - name: kube-vip
+    image: ghcr.io/kube-vip/kube-vip:v0.8.9
+    args: [manager]
+    env:
+    - name: vip_arp
+      value: \"true\"
+    - name: port
+      value: \"{{ load_balancer.port }}\"
+    - name: vip_interface
+      value: {{ load_balancer.interface }}
+    - name: cp_enable
+      value: \"true\"
+    - name: cp_namespace
+      value: kube-system
+    - name: vip_leaderelection
+      value: \"true\"
+    - name: address
+      value: {{ load_balancer.vip }}
+    securityContext:
+      capabilities:
+        add: [NET_ADMIN, NET_RAW]
+    volumeMounts:
+    - mountPath: /etc/kubernetes/admin.conf
+      name: kubeconfig
+  hostAliases:
+  - hostnames: [kubernetes]
+    ip: 127.0.0.1
+  hostNetwork: true
+  volumes:
+  - name: kubeconfig
+    hostPath:
+      path: {{ load_balancer.kubeconfig }}
+";
+
+pub(super) const HAPROXY_CONFIG_PATH: &str = "/etc/haproxy/haproxy.cfg";
+
+pub(super) const HAPROXY_CONFIG: &str = "\
+global
+    log /dev/log local0
+
+defaults
+    mode tcp
+    log global
+    option tcplog
+    timeout connect 5s
+    timeout client 1h
+    timeout server 1h
+
+frontend kube-apiserver
+    bind *:{{ load_balancer.port }}
+    default_backend kube-apiserver
+
+backend kube-apiserver
+    balance roundrobin
+{{ load_balancer.servers }}";
+
+pub(super) const KEEPALIVED_CONFIG_PATH: &str = "/etc/keepalived/keepalived.conf";
+
+pub(super) const KEEPALIVED_CHECK_PATH: &str = "/etc/keepalived/check_apiserver.sh";
+
+// fails while haproxy has no api server to forward to.
+pub(super) const KEEPALIVED_CHECK: &str = "\
+#!/bin/sh
+exec curl --silent --fail --insecure --max-time 2 --output /dev/null https://localhost:{{ load_balancer.port }}/healthz
+";
+
+// haproxy on every master balances to every api server,
+// so the virtual ip only needs a master whose haproxy answers.
+// a failing check lowers the priority below the other masters.
+pub(super) const KEEPALIVED_CONFIG: &str = "\
+global_defs {
+    enable_script_security
+    script_user root
+}
+
+vrrp_script check_apiserver {
+    script \"/etc/keepalived/check_apiserver.sh\"
+    interval 3
+    fall 3
+    rise 2
+    weight -2
+}
+
+vrrp_instance kube_apiserver {
+    state BACKUP
+    interface {{ load_balancer.interface }}
+    virtual_router_id {{ load_balancer.virtual_router_id }}
+    priority {{ load_balancer.priority }}
+    advert_int 1
+    authentication {
+        auth_type PASS
+        auth_pass {{ load_balancer.auth_pass }}
+    }
+    virtual_ipaddress {
+        {{ load_balancer.vip }}
+    }
+    track_script {
+        check_apiserver
+    }
+}
+";
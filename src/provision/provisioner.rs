@@ -1,19 +1,107 @@
 use futures::TryFutureExt;
 use thiserror::Error;
-use tracing::info_span;
+use tracing::{info, info_span};
 use tracing_futures::Instrument;
 
-use crate::provision::{
-    remote_command::{Command, RemoteCommandExecuteError},
-    RemoteCommandExecutor,
+use crate::{
+    config::LoadBalancerKind,
+    kubernetes::{ADMIN_KUBECONFIG, KUBELET_KUBECONFIG},
+    provision::{
+        distribution::PackageManager,
+        distribution::{Distribution, Repository, KUBERNETES_PACKAGES},
+        load_balancer::{
+            HAPROXY_CONFIG, HAPROXY_CONFIG_PATH, KEEPALIVED_CHECK, KEEPALIVED_CHECK_PATH,
+            KEEPALIVED_CONFIG, KEEPALIVED_CONFIG_PATH, KUBE_VIP_MANIFEST, KUBE_VIP_MANIFEST_PATH,
+        },
+        proxy::{
+            ProxyEnv, APT_PROXY_CONF_PATH, CONTAINERD_PROXY_DROP_IN_PATH,
+            KUBELET_PROXY_DROP_IN_PATH,
+        },
+        registry::{RegistryHosts, CERTS_D_DIR, MANAGED_MARKER, REMOVE_STALE_HOSTS_SCRIPT},
+        remote_command::{Command, RemoteCommandExecuteError},
+        template::{self, TemplateError, Vars},
+        RemoteCommandExecutor,
+    },
 };
 
+const CONTAINERD_MODULES_CONF: &str = "\
+overlay
+br_netfilter
+";
+
+const KUBERNETES_CRI_SYSCTL_CONF: &str = "\
+net.bridge.bridge-nf-call-iptables  = 1
+net.ipv4.ip_forward                 = 1
+net.bridge.bridge-nf-call-ip6tables = 1
+";
+
+const KUBEADM_CONFIG_PATH: &str = "/etc/kubeprovision/kubeadm.yaml";
+
+const KUBEADM_INIT_CONFIG: &str = "\
+apiVersion: kubeadm.k8s.io/v1beta3
+kind: InitConfiguration
+nodeRegistration:
+  criSocket: unix:///run/containerd/containerd.sock
+{{ node.taints }}---
+apiVersion: kubeadm.k8s.io/v1beta3
+kind: ClusterConfiguration
+clusterName: {{ cluster.name }}
+controlPlaneEndpoint: \"{{ cluster.control_plane_endpoint }}\"
+networking:
+  podSubnet: {{ cluster.pod_subnet }}
+  serviceSubnet: {{ cluster.service_subnet }}
+---
+apiVersion: kubelet.config.k8s.io/v1beta1
+kind: KubeletConfiguration
+cgroupDriver: systemd
+";
+
+// holds the bootstrap token and the certificate key, removed after joining.
+const KUBEADM_JOIN_CONFIG_PATH: &str = "/etc/kubeprovision/kubeadm-join.yaml";
+
+const KUBEADM_JOIN_CONFIG: &str = "\
+apiVersion: kubeadm.k8s.io/v1beta3
+kind: JoinConfiguration
+discovery:
+  bootstrapToken:
+    apiServerEndpoint: \"{{ join.endpoint }}\"
+    token: \"{{ join.token }}\"
+    caCertHashes:
+    - \"{{ join.ca_cert_hash }}\"
+nodeRegistration:
+  criSocket: unix:///run/containerd/containerd.sock
+{{ node.taints }}{{ join.control_plane }}";
+
+// Download the armored key $1 and write it as a keyring to $2.
+const FETCH_KEY_SCRIPT: &str = r#"set -o pipefail
+curl -fsSL "$1" | gpg --dearmor --yes -o "$2"
+"#;
+
+// removes only chains and rules of kube-proxy and CNI plugins, other host rules are kept.
+const RESET_IPTABLES_SCRIPT: &str = "\
+set -o pipefail
+for iptables in iptables ip6tables; do
+    command -v $iptables-save > /dev/null || continue
+    sudo $iptables-save | grep -v -E 'KUBE-|CNI-|FLANNEL|flanneld|cali-' | sudo $iptables-restore
+done
+if ip link show kube-ipvs0 > /dev/null 2>&1; then
+    sudo ipvsadm --clear
+    sudo ip link delete kube-ipvs0
+fi
+";
+
 #[derive(Error, Debug)]
 pub enum ProvisionError {
     #[error("command: {0}")]
     RemoteCommand(RemoteCommandExecuteError),
     #[error("ssh error")]
     Ssh { impl_err: anyhow::Error },
+    #[error("template: {0}")]
+    Template(TemplateError),
+    #[error("unsupported distribution: {0}")]
+    UnsupportedDistribution(String),
+    #[error("unexpected output of {0}")]
+    UnexpectedOutput(&'static str),
 }
 
 impl ProvisionError {
@@ -26,13 +114,75 @@ impl ProvisionError {
     }
 }
 
+/// Discovery parameters printed by `kubeadm token create --print-join-command`.
+/// The token is a secret, so it is kept out of command lines which are logged.
+#[derive(Clone)]
+pub struct JoinToken {
+    pub(super) endpoint: String,
+    pub(super) token: String,
+    pub(super) ca_cert_hash: String,
+}
+
+impl JoinToken {
+    /// Parse `kubeadm join <endpoint> --token <token> --discovery-token-ca-cert-hash <hash>`.
+    fn parse(join_command: &str) -> Option<Self> {
+        let mut args = join_command.split_whitespace();
+        let mut endpoint = None;
+        let mut token = None;
+        let mut ca_cert_hash = None;
+
+        while let Some(arg) = args.next() {
+            match arg {
+                "join" => endpoint = args.next(),
+                "--token" => token = args.next(),
+                "--discovery-token-ca-cert-hash" => ca_cert_hash = args.next(),
+                _ => {}
+            }
+        }
+        Some(Self {
+            endpoint: endpoint?.to_owned(),
+            token: token?.to_owned(),
+            ca_cert_hash: ca_cert_hash?.to_owned(),
+        })
+    }
+}
+
 pub struct Provisioner<Executor> {
     executor: Executor,
+    vars: Vars,
+    distribution: Option<Distribution>,
+    registries: Vec<RegistryHosts>,
+    proxy: Option<ProxyEnv>,
 }
 
 impl<Executor> Provisioner<Executor> {
-    pub fn new(executor: Executor) -> Self {
-        Self { executor }
+    pub fn new(executor: Executor, vars: Vars) -> Self {
+        Self {
+            executor,
+            vars,
+            distribution: None,
+            registries: Vec::new(),
+            proxy: None,
+        }
+    }
+
+    /// Use given distribution instead of detecting it from the node.
+    pub fn distribution(mut self, distribution: Option<Distribution>) -> Self {
+        self.distribution = distribution;
+        self
+    }
+
+    /// Write registry hosts.toml files when containerd is installed.
+    /// Files written earlier for registries which are no longer given are removed.
+    pub fn registries(mut self, registries: Vec<RegistryHosts>) -> Self {
+        self.registries = registries;
+        self
+    }
+
+    /// Send package manager, containerd and kubelet traffic through the proxy.
+    pub fn proxy(mut self, proxy: Option<ProxyEnv>) -> Self {
+        self.proxy = proxy;
+        self
     }
 }
 
@@ -41,76 +191,607 @@ where
     Executor: RemoteCommandExecutor,
 {
     pub async fn provision(&self) -> Result<(), ProvisionError> {
+        let distribution = self
+            .detect_distribution()
+            .instrument(info_span!("detect_distribution"))
+            .await?;
+
         self.disable_swap()
             .instrument(info_span!("disable_swap"))
             .and_then(|_| {
-                self.install_containerd()
+                self.configure_proxy(distribution)
+                    .instrument(info_span!("configure_proxy"))
+            })
+            .and_then(|_| {
+                self.add_repositories(distribution)
+                    .instrument(info_span!("add_repositories", %distribution))
+            })
+            .and_then(|_| {
+                self.install_containerd(distribution)
                     .instrument(info_span!("install_containerd"))
             })
+            .and_then(|_| {
+                self.install_kubernetes(distribution)
+                    .instrument(info_span!("install_kubernetes"))
+            })
             .await
     }
 
+    async fn detect_distribution(&self) -> Result<Distribution, ProvisionError> {
+        if let Some(distribution) = self.distribution {
+            return Ok(distribution);
+        }
+
+        let os_release = self.executor.fetch("/etc/os-release").await?;
+        let os_release = String::from_utf8_lossy(&os_release);
+        Distribution::from_os_release(&os_release).ok_or_else(|| {
+            let id = os_release
+                .lines()
+                .find_map(|line| line.strip_prefix("ID="))
+                .unwrap_or("unknown");
+            ProvisionError::UnsupportedDistribution(id.trim_matches('"').to_owned())
+        })
+    }
+
     async fn disable_swap(&self) -> Result<(), ProvisionError> {
         self.executor
             .execute(Command::Sudo(&["swapoff", "-a"]))
             .await
     }
 
-    async fn install_containerd(&self) -> Result<(), ProvisionError> {
+    /// Write proxy settings before anything is downloaded.
+    async fn configure_proxy(&self, distribution: Distribution) -> Result<(), ProvisionError> {
+        let proxy = match self.proxy.as_ref() {
+            Some(proxy) => proxy,
+            None => return Ok(()),
+        };
+
+        match distribution.package_manager() {
+            PackageManager::Apt => {
+                // the proxy urls may hold credentials.
+                self.put_file(APT_PROXY_CONF_PATH, &proxy.apt_conf(), 0o600)
+                    .await?;
+            }
+            package_manager => {
+                let path = match package_manager {
+                    PackageManager::Dnf => "/etc/dnf/dnf.conf",
+                    _ => "/etc/yum.conf",
+                };
+                // the proxy url may hold credentials, so it is not put on a command line.
+                let current = self.executor.fetch(path).await?;
+                if let Some(conf) = proxy.yum_conf(&String::from_utf8_lossy(&current)) {
+                    let mode = if proxy.yum_has_credentials() {
+                        0o600
+                    } else {
+                        0o644
+                    };
+                    self.put_file(path, &conf, mode).await?;
+                }
+            }
+        }
+
+        let drop_in = proxy.systemd_drop_in();
+        self.put_file(CONTAINERD_PROXY_DROP_IN_PATH, &drop_in, 0o600)
+            .await?;
+        self.put_file(KUBELET_PROXY_DROP_IN_PATH, &drop_in, 0o600)
+            .await?;
+        self.executor
+            .execute(Command::Sudo(&["systemctl", "daemon-reload"]))
+            .await
+    }
+
+    async fn add_repositories(&self, distribution: Distribution) -> Result<(), ProvisionError> {
+        let package_manager = distribution.package_manager();
+
+        if distribution.has_selinux() {
+            // https://kubernetes.io/docs/setup/production-environment/tools/kubeadm/install-kubeadm/
+            self.executor
+                .execute(Command::Bash(
+                    "if [ \"$(getenforce)\" = Enforcing ]; then sudo setenforce 0; fi",
+                ))
+                .await?;
+            self.executor
+                .execute(Command::Sudo(&[
+                    "sed",
+                    "-i",
+                    "s/^SELINUX=enforcing$/SELINUX=permissive/",
+                    "/etc/selinux/config",
+                ]))
+                .await?;
+        }
+
+        self.update_packages(distribution).await?;
+        if !package_manager.prerequisites().is_empty() {
+            self.executor
+                .execute(Command::Sudo(
+                    &package_manager.install(package_manager.prerequisites()),
+                ))
+                .await?;
+        }
+
+        for repository in distribution.repositories() {
+            self.add_repository(&repository).await?;
+        }
+
+        self.update_packages(distribution).await
+    }
+
+    async fn add_repository(&self, repository: &Repository) -> Result<(), ProvisionError> {
+        if let Some((url, keyring)) = repository.key {
+            let url = template::render(url, &self.vars).map_err(ProvisionError::Template)?;
+            let dir = keyring.rsplit_once('/').map_or("/", |(dir, _)| dir);
+            self.executor
+                .execute(Command::Sudo(&["mkdir", "-p", "-m", "755", dir]))
+                .await?;
+            // curl does not read the apt proxy configuration.
+            let env = self
+                .proxy
+                .iter()
+                .flat_map(ProxyEnv::variables)
+                .collect::<Vec<_>>();
+            let mut command = vec!["env"];
+            command.extend(env.iter().map(String::as_str));
+            command.extend_from_slice(&[
+                "bash",
+                "-c",
+                FETCH_KEY_SCRIPT,
+                "kubeprovision",
+                &url,
+                keyring,
+            ]);
+            self.executor.execute(Command::Sudo(&command)).await?;
+        }
+        self.put_template(repository.path, repository.template, 0o644)
+            .await
+    }
+
+    async fn install_containerd(&self, distribution: Distribution) -> Result<(), ProvisionError> {
         // https://v1-23.docs.kubernetes.io/docs/setup/production-environment/container-runtimes/#containerd
+        let package_manager = distribution.package_manager();
 
-        let put_containerd_conf = "cat <<EOF | sudo tee /etc/modules-load.d/containerd.conf
-    overlay
-    br_netfilter
-    EOF";
+        self.put_template(
+            "/etc/modules-load.d/containerd.conf",
+            CONTAINERD_MODULES_CONF,
+            0o644,
+        )
+        .and_then(|_| {
+            self.executor
+                .execute(Command::Sudo(&["modprobe", "overlay"]))
+        })
+        .and_then(|_| {
+            self.executor
+                .execute(Command::Sudo(&["modprobe", "br_netfilter"]))
+        })
+        .and_then(|_| {
+            self.put_template(
+                "/etc/sysctl.d/99-kubernetes-cri.conf",
+                KUBERNETES_CRI_SYSCTL_CONF,
+                0o644,
+            )
+        })
+        .and_then(|_| {
+            self.executor
+                .execute(Command::Sudo(&["sysctl", "--system"]))
+        })
+        .and_then(|_| async move {
+            self.executor
+                .execute(Command::Sudo(
+                    &package_manager.install(&[distribution.containerd_package()]),
+                ))
+                .await
+        })
+        .and_then(|_| {
+            self.executor
+                .execute(Command::Sudo(&["mkdir", "-p", "/etc/containerd"]))
+        })
+        .and_then(|_| {
+            self.executor.execute(Command::Bash(
+                "containerd config default | sudo tee /etc/containerd/config.toml",
+            ))
+        })
+        .and_then(|_| {
+            // kubelet uses the systemd cgroup driver. both must agree.
+            self.executor.execute(Command::Sudo(&[
+                "sed",
+                "-i",
+                "s/SystemdCgroup = false/SystemdCgroup = true/",
+                "/etc/containerd/config.toml",
+            ]))
+        })
+        .and_then(|_| self.configure_registries())
+        .and_then(|_| self.enable_service("containerd"))
+        .await
+    }
 
-        let put_cri_conf = "cat <<EOF | sudo tee /etc/sysctl.d/99-kubernetes-cri.conf
-        net.bridge.bridge-nf-call-iptables  = 1
-        net.ipv4.ip_forward                 = 1
-        net.bridge.bridge-nf-call-ip6tables = 1
-        EOF";
+    async fn configure_registries(&self) -> Result<(), ProvisionError> {
+        // containerd 1.x leaves config_path empty by default and ignores certs.d.
+        let config_path = format!("s|config_path = \"\"|config_path = \"{CERTS_D_DIR}\"|");
+        self.executor
+            .execute(Command::Sudo(&[
+                "sed",
+                "-i",
+                &config_path,
+                "/etc/containerd/config.toml",
+            ]))
+            .await?;
+
+        for registry in self.registries.iter() {
+            let mode = if registry.secret { 0o600 } else { 0o644 };
+            self.put_file(&registry.path, &registry.content, mode)
+                .await?;
+        }
+
+        // registries removed from the configuration.
+        let mut command = vec![
+            "bash",
+            "-c",
+            REMOVE_STALE_HOSTS_SCRIPT,
+            "kubeprovision",
+            CERTS_D_DIR,
+            MANAGED_MARKER,
+        ];
+        command.extend(
+            self.registries
+                .iter()
+                .map(|registry| registry.path.as_str()),
+        );
+        self.executor.execute(Command::Sudo(&command)).await
+    }
+
+    async fn install_kubernetes(&self, distribution: Distribution) -> Result<(), ProvisionError> {
+        let package_manager = distribution.package_manager();
 
         self.executor
-            .execute(Command::Bash(put_containerd_conf))
-            .and_then(|_| {
-                self.executor
-                    .execute(Command::Sudo(&["modprobe", "overlay"]))
-            })
-            .and_then(|_| {
-                self.executor
-                    .execute(Command::Sudo(&["modprobe", "br_netfilter"]))
-            })
-            .and_then(|_| self.executor.execute(Command::Bash(put_cri_conf)))
-            .and_then(|_| {
-                self.executor
-                    .execute(Command::Sudo(&["sysctl", "--system"]))
-            })
-            .and_then(|_| self.executor.execute(Command::Sudo(&["apt-get", "update"])))
-            .and_then(|_| {
-                self.executor.execute(Command::Sudo(&[
-                    "apt-get",
-                    "install",
-                    "containerd",
-                    "--yes",
-                ]))
-            })
-            .and_then(|_| {
+            .execute(Command::Sudo(
+                &package_manager.install_held(KUBERNETES_PACKAGES),
+            ))
+            .await?;
+        if let Some(hold) = package_manager.hold(KUBERNETES_PACKAGES) {
+            self.executor.execute(Command::Sudo(&hold)).await?;
+        }
+        // kubelet restarts in a crash loop until kubeadm init or join configures it.
+        self.executor
+            .execute(Command::Sudo(&["systemctl", "enable", "kubelet"]))
+            .await
+    }
+
+    /// Initialize the control plane with kubeadm and apply the CNI plugin.
+    /// Does nothing if the node is already initialized.
+    pub async fn init_control_plane(&self, cni_manifest: &str) -> Result<(), ProvisionError> {
+        if self.exists(ADMIN_KUBECONFIG).await? {
+            info!("control plane is already initialized");
+            return Ok(());
+        }
+
+        self.put_template(KUBEADM_CONFIG_PATH, KUBEADM_INIT_CONFIG, 0o600)
+            .await?;
+        let command = Command::Sudo(&[
+            "kubeadm",
+            "init",
+            "--config",
+            KUBEADM_CONFIG_PATH,
+            // lets other masters download the certificates when they join.
+            "--upload-certs",
+        ]);
+        let log = command.to_string();
+        // stdout is not logged since it ends with the join commands holding the bootstrap token
+        // and the certificate key.
+        let output = self.executor.output(command).await?;
+        if !output.status.success() {
+            return Err(ProvisionError::RemoteCommand(
+                RemoteCommandExecuteError::new(log, output.stderr),
+            ));
+        }
+        info!("success {log}");
+        // the manifest may be downloaded from the internet.
+        let env = self
+            .proxy
+            .iter()
+            .flat_map(ProxyEnv::variables)
+            .collect::<Vec<_>>();
+        let mut command = vec!["env"];
+        command.extend(env.iter().map(String::as_str));
+        command.extend_from_slice(&[
+            "kubectl",
+            "--kubeconfig",
+            ADMIN_KUBECONFIG,
+            "apply",
+            "--filename",
+            cni_manifest,
+        ]);
+        self.executor.execute(Command::Sudo(&command)).await
+    }
+
+    /// Run the control plane load balancer on the master. `load_balancer.*` vars must be set.
+    pub async fn deploy_load_balancer(&self, kind: LoadBalancerKind) -> Result<(), ProvisionError> {
+        match kind {
+            // kubelet starts the static pod, also before kubeadm init.
+            LoadBalancerKind::KubeVip => {
+                self.put_template(KUBE_VIP_MANIFEST_PATH, KUBE_VIP_MANIFEST, 0o600)
+                    .await
+            }
+            LoadBalancerKind::Haproxy => {
+                let distribution = self.detect_distribution().await?;
                 self.executor
-                    .execute(Command::Sudo(&["mkdir", "-p", "/etc/containerd"]))
+                    .execute(Command::Sudo(
+                        &distribution
+                            .package_manager()
+                            .install(&["haproxy", "keepalived"]),
+                    ))
+                    .await?;
+                self.put_template(HAPROXY_CONFIG_PATH, HAPROXY_CONFIG, 0o644)
+                    .await?;
+                self.put_template(KEEPALIVED_CHECK_PATH, KEEPALIVED_CHECK, 0o755)
+                    .await?;
+                // holds the VRRP password.
+                self.put_template(KEEPALIVED_CONFIG_PATH, KEEPALIVED_CONFIG, 0o600)
+                    .await?;
+                self.enable_service("haproxy").await?;
+                self.enable_service("keepalived").await
+            }
+        }
+    }
+
+    /// Create a bootstrap token for nodes to join the cluster.
+    pub async fn join_token(&self) -> Result<JoinToken, ProvisionError> {
+        let command = Command::Sudo(&["kubeadm", "token", "create", "--print-join-command"]);
+        let log = command.to_string();
+        let output = self.executor.output(command).await?;
+
+        if output.status.success() {
+            JoinToken::parse(&String::from_utf8_lossy(&output.stdout))
+                .ok_or(ProvisionError::UnexpectedOutput("kubeadm token create"))
+        } else {
+            Err(ProvisionError::RemoteCommand(
+                RemoteCommandExecuteError::new(log, output.stderr),
+            ))
+        }
+    }
+
+    /// Upload control plane certificates again and return the key to decrypt them.
+    /// Certificates uploaded by `kubeadm init` are deleted after two hours.
+    pub async fn certificate_key(&self) -> Result<String, ProvisionError> {
+        let command =
+            Command::Sudo(&["kubeadm", "init", "phase", "upload-certs", "--upload-certs"]);
+        let log = command.to_string();
+        let output = self.executor.output(command).await?;
+
+        if output.status.success() {
+            // the key is printed on the last line.
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            Ok(stdout.trim().lines().last().unwrap_or_default().to_owned())
+        } else {
+            Err(ProvisionError::RemoteCommand(
+                RemoteCommandExecuteError::new(log, output.stderr),
+            ))
+        }
+    }
+
+    /// Join the node to the cluster. Does nothing if the node has already joined.
+    pub async fn join(&self, join_token: &JoinToken) -> Result<(), ProvisionError> {
+        self.join_with_config(join_token, None).await
+    }
+
+    /// Join the node as an additional master. Does nothing if the node has already joined.
+    pub async fn join_control_plane(
+        &self,
+        join_token: &JoinToken,
+        certificate_key: &str,
+    ) -> Result<(), ProvisionError> {
+        self.join_with_config(join_token, Some(certificate_key))
+            .await
+    }
+
+    /// Run `kubeadm join` with the secrets in a config file instead of the command line.
+    async fn join_with_config(
+        &self,
+        join_token: &JoinToken,
+        certificate_key: Option<&str>,
+    ) -> Result<(), ProvisionError> {
+        if self.exists(KUBELET_KUBECONFIG).await? {
+            info!("node has already joined");
+            return Ok(());
+        }
+
+        let vars = self.vars.clone().join(join_token, certificate_key);
+        let content =
+            template::render(KUBEADM_JOIN_CONFIG, &vars).map_err(ProvisionError::Template)?;
+        self.executor
+            .execute(Command::PutFile {
+                path: KUBEADM_JOIN_CONFIG_PATH,
+                content: content.as_bytes(),
+                mode: 0o600,
+                owner: "root:root",
             })
-            .and_then(|_| {
-                self.executor.execute(Command::Bash(
-                    "containerd config default | sudo tee /etc/containerd/config.toml",
+            .await?;
+        let joined = self
+            .executor
+            .execute(Command::Sudo(&[
+                "kubeadm",
+                "join",
+                "--config",
+                KUBEADM_JOIN_CONFIG_PATH,
+            ]))
+            .await;
+        // removed also when joining failed.
+        self.executor
+            .execute(Command::Sudo(&["rm", "-f", KUBEADM_JOIN_CONFIG_PATH]))
+            .await?;
+        joined
+    }
+
+    /// Switch the kubernetes repository to `kubernetes.version` and upgrade kubeadm.
+    pub async fn upgrade_kubeadm(&self) -> Result<(), ProvisionError> {
+        let distribution = self.detect_distribution().await?;
+
+        self.add_repository(&distribution.kubernetes_repository())
+            .await?;
+        self.update_packages(distribution).await?;
+        self.executor
+            .execute(Command::Sudo(
+                &distribution.package_manager().install_held(&["kubeadm"]),
+            ))
+            .await
+    }
+
+    /// Upgrade the control plane of the first master to the version of the installed kubeadm.
+    pub async fn upgrade_apply(&self) -> Result<(), ProvisionError> {
+        let command = Command::Sudo(&["kubeadm", "version", "--output", "short"]);
+        let log = command.to_string();
+        let output = self.executor.output(command).await?;
+        if !output.status.success() {
+            return Err(ProvisionError::RemoteCommand(
+                RemoteCommandExecuteError::new(log, output.stderr),
+            ));
+        }
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+
+        self.executor
+            .execute(Command::Sudo(&["kubeadm", "upgrade", "plan", &version]))
+            .await?;
+        self.executor
+            .execute(Command::Sudo(&[
+                "kubeadm", "upgrade", "apply", "--yes", &version,
+            ]))
+            .await
+    }
+
+    /// Upgrade the local control plane components or kubelet config of the node
+    /// other than the first master.
+    pub async fn upgrade_node(&self) -> Result<(), ProvisionError> {
+        self.executor
+            .execute(Command::Sudo(&["kubeadm", "upgrade", "node"]))
+            .await
+    }
+
+    /// Upgrade kubelet and kubectl and restart kubelet. The node should be drained beforehand.
+    pub async fn upgrade_kubelet(&self) -> Result<(), ProvisionError> {
+        let distribution = self.detect_distribution().await?;
+
+        self.executor
+            .execute(Command::Sudo(
+                &distribution
+                    .package_manager()
+                    .install_held(&["kubelet", "kubectl"]),
+            ))
+            .await?;
+        self.executor
+            .execute(Command::Sudo(&["systemctl", "daemon-reload"]))
+            .await?;
+        self.enable_service("kubelet").await
+    }
+
+    /// Revert kubeadm init or join so that the node can be provisioned again.
+    /// Packages are removed as well if purge is set.
+    pub async fn reset(&self, purge: bool) -> Result<(), ProvisionError> {
+        // kubeadm is missing if the node was never provisioned or already purged.
+        let has_kubeadm = self
+            .executor
+            .output(Command::Bash("command -v kubeadm"))
+            .await?
+            .status
+            .success();
+        if has_kubeadm {
+            self.executor
+                .execute(Command::Sudo(&["kubeadm", "reset", "--force"]))
+                .await?;
+        }
+        self.executor
+            .execute(Command::Sudo(&[
+                "rm",
+                "-rf",
+                "/etc/cni/net.d",
+                KUBEADM_CONFIG_PATH,
+                KUBEADM_JOIN_CONFIG_PATH,
+            ]))
+            .await?;
+        // kubeadm reset leaves rules created by kube-proxy and the CNI plugin.
+        self.executor
+            .execute(Command::Bash(RESET_IPTABLES_SCRIPT))
+            .await?;
+
+        if purge {
+            let distribution = self.detect_distribution().await?;
+            self.executor
+                .execute(Command::Sudo(
+                    &distribution.package_manager().remove(KUBERNETES_PACKAGES),
                 ))
-            })
-            .and_then(|_| {
-                self.executor
-                    .execute(Command::Sudo(&["systemctl", "restart", "containerd"]))
-            })
-            .and_then(|_| {
-                self.executor
-                    .execute(Command::Executable("service", &["containerd", "status"]))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool, ProvisionError> {
+        let output = self
+            .executor
+            .output(Command::Sudo(&["test", "-e", path]))
+            .await?;
+        Ok(output.status.success())
+    }
+
+    async fn update_packages(&self, distribution: Distribution) -> Result<(), ProvisionError> {
+        match distribution.package_manager().update() {
+            Some(update) => self.executor.execute(Command::Sudo(update)).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Enable and restart the systemd service and make sure it is active.
+    async fn enable_service(&self, name: &str) -> Result<(), ProvisionError> {
+        for action in ["enable", "restart", "is-active"] {
+            self.executor
+                .execute(Command::Sudo(&["systemctl", action, name]))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn put_file(&self, path: &str, content: &str, mode: u32) -> Result<(), ProvisionError> {
+        self.executor
+            .execute(Command::PutFile {
+                path,
+                content: content.as_bytes(),
+                mode,
+                owner: "root:root",
             })
             .await
     }
+
+    async fn put_template(
+        &self,
+        path: &str,
+        template: &str,
+        mode: u32,
+    ) -> Result<(), ProvisionError> {
+        let content = template::render(template, &self.vars).map_err(ProvisionError::Template)?;
+
+        self.put_file(path, &content, mode).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_token_parse() {
+        let join_token = JoinToken::parse(
+            "kubeadm join 10.0.1.10:6443 --token abcdef.0123456789abcdef \
+             --discovery-token-ca-cert-hash sha256:0a1b2c \n",
+        )
+        .unwrap();
+        assert_eq!(join_token.endpoint, "10.0.1.10:6443");
+        assert_eq!(join_token.token, "abcdef.0123456789abcdef");
+        assert_eq!(join_token.ca_cert_hash, "sha256:0a1b2c");
+    }
+
+    #[test]
+    fn join_token_parse_incomplete() {
+        assert!(
+            JoinToken::parse("kubeadm join 10.0.1.10:6443 --token abcdef.0123456789abcdef")
+                .is_none()
+        );
+        assert!(JoinToken::parse("").is_none());
+    }
 }
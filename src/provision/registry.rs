@@ -0,0 +1,172 @@
+use std::fmt::Write;
+
+use crate::config::RegistryConfig;
+
+/// Directory containerd reads per registry `hosts.toml` from.
+pub const CERTS_D_DIR: &str = "/etc/containerd/certs.d";
+
+/// First line of every `hosts.toml` written by kubeprovision.
+/// Files without it are left alone as they are managed by someone else.
+pub const MANAGED_MARKER: &str = "# managed by kubeprovision";
+
+// Remove hosts.toml files under $1 starting with the marker $2 except the paths given after them.
+pub const REMOVE_STALE_HOSTS_SCRIPT: &str = r#"set -e
+shopt -s nullglob
+dir=$1
+marker=$2
+shift 2
+for file in "$dir"/*/hosts.toml; do
+    [ "$(head -n 1 "$file")" = "$marker" ] || continue
+    for keep in "$@"; do
+        [ "$file" = "$keep" ] && continue 2
+    done
+    rm -f "$file"
+    rmdir --ignore-fail-on-non-empty "$(dirname "$file")"
+done
+"#;
+
+// the docker.io namespace is served by a different host.
+const DOCKER_HUB_HOST: &str = "docker.io";
+const DOCKER_HUB_SERVER: &str = "https://registry-1.docker.io";
+
+/// `hosts.toml` of a registry ready to be written to nodes.
+#[derive(Debug, Clone)]
+pub struct RegistryHosts {
+    pub path: String,
+    pub content: String,
+    /// Whether content contains credentials and must be readable only by root.
+    pub secret: bool,
+}
+
+impl RegistryHosts {
+    /// Render `hosts.toml` of the registry.
+    /// Credentials are read here so that a missing one fails before any node is touched.
+    pub fn from_config(registry: &RegistryConfig) -> anyhow::Result<Self> {
+        let (namespace, server) = match registry.host.split_once("://") {
+            Some((_, host)) => (host, registry.host.clone()),
+            None if registry.host == DOCKER_HUB_HOST => {
+                (DOCKER_HUB_HOST, DOCKER_HUB_SERVER.to_owned())
+            }
+            None => (registry.host.as_str(), format!("https://{}", registry.host)),
+        };
+
+        let mut content = format!("{MANAGED_MARKER}\nserver = {}\n", toml_string(&server));
+        if registry.insecure {
+            content.push_str("skip_verify = true\n");
+        }
+        if let Some(auth) = registry.auth.as_ref() {
+            let credentials = format!("{}:{}", auth.username, auth.password()?);
+            content.push_str("\n[header]\n");
+            let authorization = format!("Basic {}", base64::encode(credentials));
+            writeln!(content, "  authorization = {}", toml_string(&authorization))?;
+        }
+        for mirror in registry.mirrors.iter() {
+            writeln!(content, "\n[host.{}]", toml_string(mirror))?;
+            content.push_str("  capabilities = [\"pull\", \"resolve\"]\n");
+            if registry.insecure {
+                content.push_str("  skip_verify = true\n");
+            }
+        }
+
+        Ok(RegistryHosts {
+            path: format!("{CERTS_D_DIR}/{namespace}/hosts.toml"),
+            content,
+            secret: registry.auth.is_some(),
+        })
+    }
+}
+
+/// Quote value as a TOML basic string.
+fn toml_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use super::*;
+
+    fn registry(host: &str, mirrors: &[&str]) -> RegistryConfig {
+        RegistryConfig {
+            host: host.to_owned(),
+            mirrors: mirrors.iter().map(|mirror| (*mirror).to_owned()).collect(),
+            insecure: false,
+            auth: None,
+        }
+    }
+
+    #[test]
+    fn docker_hub_and_mirrors() {
+        let hosts =
+            RegistryHosts::from_config(&registry("docker.io", &["https://mirror.internal"]))
+                .unwrap();
+        assert_eq!(hosts.path, "/etc/containerd/certs.d/docker.io/hosts.toml");
+        assert_eq!(
+            hosts.content,
+            "# managed by kubeprovision\n\
+             server = \"https://registry-1.docker.io\"\n\
+             \n\
+             [host.\"https://mirror.internal\"]\n  \
+             capabilities = [\"pull\", \"resolve\"]\n"
+        );
+        assert!(!hosts.secret);
+    }
+
+    #[test]
+    fn plain_http_registry() {
+        let mut config = registry("http://registry.internal:5000", &[]);
+        config.insecure = true;
+        let hosts = RegistryHosts::from_config(&config).unwrap();
+        assert_eq!(
+            hosts.path,
+            "/etc/containerd/certs.d/registry.internal:5000/hosts.toml"
+        );
+        assert_eq!(
+            hosts.content,
+            "# managed by kubeprovision\n\
+             server = \"http://registry.internal:5000\"\n\
+             skip_verify = true\n"
+        );
+    }
+
+    #[test]
+    fn basic_auth_header() {
+        let mut password_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(password_file, "p\"ss").unwrap();
+        let config = serde_yaml::from_str::<RegistryConfig>(&format!(
+            "host: registry.internal\nauth:\n  username: user\n  passwordFile: {:?}\n",
+            password_file.path()
+        ))
+        .unwrap();
+        let hosts = RegistryHosts::from_config(&config).unwrap();
+        // base64 of user:p"ss
+        assert!(hosts
+            .content
+            .ends_with("\n[header]\n  authorization = \"Basic dXNlcjpwInNz\"\n"));
+        assert!(hosts.secret);
+    }
+
+    #[test]
+    fn toml_string_escapes() {
+        assert_eq!(toml_string("mirror.internal"), "\"mirror.internal\"");
+        assert_eq!(
+            toml_string("a\"]\n[b\\c\u{7}"),
+            "\"a\\\"]\\n[b\\\\c\\u0007\""
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::{collections::BTreeMap, net::IpAddr};
+
+use thiserror::Error;
+
+use crate::{
+    config::{ClusterConfig, LoadBalancerConfig, Taint, TaintEffect},
+    node::{Node, NodeRole},
+    provision::JoinToken,
+};
+
+#[derive(Error, Debug)]
+pub enum TemplateError {
+    #[error("undefined variable {0}")]
+    UndefinedVariable(String),
+    #[error("unclosed placeholder at byte {0}")]
+    UnclosedPlaceholder(usize),
+}
+
+const CONTROL_PLANE_TAINT_KEY: &str = "node-role.kubernetes.io/control-plane";
+
+/// Variables which templates can reference as `{{ node.id }}`.
+#[derive(Debug, Clone, Default)]
+pub struct Vars(BTreeMap<String, String>);
+
+impl Vars {
+    pub fn node(mut self, role: NodeRole, node: &impl Node) -> Self {
+        self.insert("node.id", node.id());
+        self.insert("node.role", role);
+        if let Some(ip) = node.public_ip() {
+            self.insert("node.public_ip", ip);
+        }
+        // kubeadm applies its default taints unless taints are given.
+        self.insert("node.taints", "");
+        self
+    }
+
+    /// `nodeRegistration.taints` of kubeadm so that the node is tainted as soon as it registers.
+    /// Masters keep the control plane taint kubeadm would add by default.
+    pub fn taints(mut self, role: NodeRole, taints: &[Taint]) -> Self {
+        if taints.is_empty() {
+            return self;
+        }
+        let mut taints = taints.to_vec();
+        if role == NodeRole::Master && !taints.iter().any(|t| t.key == CONTROL_PLANE_TAINT_KEY) {
+            taints.insert(
+                0,
+                Taint {
+                    key: CONTROL_PLANE_TAINT_KEY.to_owned(),
+                    value: None,
+                    effect: TaintEffect::NoSchedule,
+                },
+            );
+        }
+        let mut yaml = String::from("  taints:\n");
+        for taint in taints {
+            yaml.push_str(&format!("  - key: \"{}\"\n", taint.key));
+            if let Some(value) = taint.value.as_ref() {
+                yaml.push_str(&format!("    value: \"{value}\"\n"));
+            }
+            yaml.push_str(&format!("    effect: {:?}\n", taint.effect));
+        }
+        self.insert("node.taints", yaml);
+        self
+    }
+
+    pub fn cluster(mut self, cluster: &ClusterConfig) -> Self {
+        self.insert("cluster.name", &cluster.name);
+        self.insert("cluster.pod_subnet", &cluster.pod_subnet);
+        self.insert("cluster.service_subnet", &cluster.service_subnet);
+        // kubeadm treats empty endpoint as unset.
+        self.insert(
+            "cluster.control_plane_endpoint",
+            cluster.control_plane_endpoint().unwrap_or_default(),
+        );
+        self.insert("kubernetes.version", &cluster.kubernetes_version);
+        self
+    }
+
+    /// Discovery and control plane secrets of kubeadm join.
+    pub fn join(mut self, join_token: &JoinToken, certificate_key: Option<&str>) -> Self {
+        self.insert("join.endpoint", &join_token.endpoint);
+        self.insert("join.token", &join_token.token);
+        self.insert("join.ca_cert_hash", &join_token.ca_cert_hash);
+        let control_plane = certificate_key
+            .map(|key| format!("controlPlane:\n  certificateKey: \"{key}\"\n"))
+            .unwrap_or_default();
+        self.insert("join.control_plane", control_plane);
+        self
+    }
+
+    /// Variables of the load balancer running on the master.
+    /// priority decides which master holds the virtual ip, higher wins.
+    pub fn load_balancer(
+        mut self,
+        load_balancer: &LoadBalancerConfig,
+        servers: &[IpAddr],
+        priority: u8,
+        kubeconfig: &str,
+    ) -> Self {
+        self.insert("load_balancer.vip", load_balancer.vip);
+        self.insert("load_balancer.interface", &load_balancer.interface);
+        self.insert("load_balancer.port", load_balancer.port());
+        self.insert("load_balancer.priority", priority);
+        self.insert(
+            "load_balancer.virtual_router_id",
+            load_balancer.virtual_router_id(),
+        );
+        self.insert("load_balancer.auth_pass", load_balancer.auth_pass());
+        self.insert("load_balancer.kubeconfig", kubeconfig);
+        // haproxy backend lines.
+        let servers = servers
+            .iter()
+            .enumerate()
+            .map(|(index, ip)| format!("    server master-{index} {ip}:6443 check\n"))
+            .collect::<String>();
+        self.insert("load_balancer.servers", servers);
+        self
+    }
+
+    fn insert(&mut self, key: &str, value: impl ToString) {
+        self.0.insert(key.to_owned(), value.to_string());
+    }
+}
+
+/// Render `{{ name }}` placeholders in template with given vars.
+/// Everything outside placeholders is copied byte for byte.
+pub fn render(template: &str, vars: &Vars) -> Result<String, TemplateError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..].find("}}").ok_or_else(|| {
+            TemplateError::UnclosedPlaceholder(template.len() - rest.len() + start)
+        })?;
+        let name = rest[start + 2..start + end].trim();
+        let value = vars
+            .0
+            .get(name)
+            .ok_or_else(|| TemplateError::UndefinedVariable(name.to_owned()))?;
+        rendered.push_str(value);
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_placeholders() {
+        let vars = Vars::default().cluster(&ClusterConfig::default());
+        assert_eq!(
+            render(
+                "v{{ kubernetes.version }}/{{cluster.name}} {{ cluster.control_plane_endpoint }}.",
+                &vars
+            )
+            .unwrap(),
+            "v1.32/kubernetes ."
+        );
+        assert_eq!(
+            render("no {placeholders}", &vars).unwrap(),
+            "no {placeholders}"
+        );
+    }
+
+    #[test]
+    fn render_errors() {
+        let vars = Vars::default();
+        assert!(matches!(
+            render("{{ node.id }}", &vars),
+            Err(TemplateError::UndefinedVariable(name)) if name == "node.id"
+        ));
+        assert!(matches!(
+            render("ok {{ node.id", &vars),
+            Err(TemplateError::UnclosedPlaceholder(3))
+        ));
+    }
+
+    #[test]
+    fn taints_keep_control_plane_taint_of_masters() {
+        let taints = [Taint {
+            key: "dedicated".to_owned(),
+            value: Some("infra".to_owned()),
+            effect: TaintEffect::NoExecute,
+        }];
+        let vars = Vars::default().taints(NodeRole::Master, &taints);
+        assert_eq!(
+            render("{{ node.taints }}", &vars).unwrap(),
+            "  taints:\n  \
+             - key: \"node-role.kubernetes.io/control-plane\"\n    effect: NoSchedule\n  \
+             - key: \"dedicated\"\n    value: \"infra\"\n    effect: NoExecute\n"
+        );
+
+        let vars = Vars::default().taints(NodeRole::Worker, &taints);
+        assert!(!render("{{ node.taints }}", &vars)
+            .unwrap()
+            .contains("control-plane"));
+    }
+}
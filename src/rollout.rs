@@ -0,0 +1,226 @@
+use std::{
+    future::Future,
+    process::Output,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::{
+    future::{abortable, AbortHandle, Aborted, BoxFuture},
+    stream::FuturesUnordered,
+    FutureExt, StreamExt,
+};
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+use crate::node::{Node, NodeId, NodeRole};
+
+/// How a task is rolled out across nodes.
+#[derive(Debug, Clone, Default)]
+pub struct RolloutStrategy {
+    /// Maximum number of nodes running the task at the same time. None means unlimited.
+    pub parallel: Option<usize>,
+    /// Split nodes into batches of this size. Next batch starts after the previous one completed.
+    pub batch_size: Option<usize>,
+    /// Wait time between batches.
+    pub batch_pause: Duration,
+    /// Cancel running tasks once any node fails.
+    pub fail_fast: bool,
+}
+
+/// Check run after each batch including the last one. Remaining batches are skipped if it fails.
+pub type HealthGate<N> =
+    Box<dyn Fn(Vec<(NodeRole, N)>) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
+/// Value produced by a task on a node.
+pub trait TaskOutput: Send + 'static {
+    fn succeeded(&self) -> bool {
+        true
+    }
+}
+
+impl TaskOutput for () {}
+
+impl TaskOutput for Output {
+    fn succeeded(&self) -> bool {
+        self.status.success()
+    }
+}
+
+/// Result of the task on a node.
+pub struct NodeExecution<R> {
+    pub role: NodeRole,
+    pub node_id: NodeId,
+    pub outcome: ExecOutcome<R>,
+    pub elapsed: Duration,
+}
+
+pub enum ExecOutcome<R> {
+    /// Task ran to completion, successfully or not.
+    Completed(R),
+    /// Task could not be completed, e.g. ssh connection failed.
+    Error(anyhow::Error),
+    /// Task was aborted because another node failed.
+    Cancelled,
+    /// Task was not started because an earlier batch failed.
+    Skipped,
+}
+
+impl<R: TaskOutput> NodeExecution<R> {
+    pub fn succeeded(&self) -> bool {
+        matches!(&self.outcome, ExecOutcome::Completed(output) if output.succeeded())
+    }
+}
+
+pub struct RolloutReport<R> {
+    pub executions: Vec<NodeExecution<R>>,
+    /// Why remaining batches were skipped, or why the health gate failed after the last batch.
+    pub halted: Option<anyhow::Error>,
+}
+
+impl<R: TaskOutput> RolloutReport<R> {
+    pub fn failed(&self) -> usize {
+        self.executions.iter().filter(|e| !e.succeeded()).count()
+    }
+}
+
+pub struct Rollout<N> {
+    strategy: RolloutStrategy,
+    health_gate: Option<HealthGate<N>>,
+}
+
+impl<N> Rollout<N>
+where
+    N: Node + Clone + Send + 'static,
+{
+    pub fn new(strategy: RolloutStrategy) -> Self {
+        Self {
+            strategy,
+            health_gate: None,
+        }
+    }
+
+    pub fn health_gate(mut self, gate: HealthGate<N>) -> Self {
+        self.health_gate = Some(gate);
+        self
+    }
+
+    /// Run task on each node following the strategy.
+    /// Executions are reported in the order of given nodes.
+    pub async fn run<F, Fut, R>(&self, nodes: Vec<(NodeRole, N)>, task: F) -> RolloutReport<R>
+    where
+        F: Fn(NodeRole, N) -> Fut,
+        Fut: Future<Output = anyhow::Result<R>> + Send + 'static,
+        R: TaskOutput,
+    {
+        let batch_size = self.strategy.batch_size.unwrap_or(nodes.len()).max(1);
+        let batch_count = nodes.len().div_ceil(batch_size);
+        let mut executions = Vec::with_capacity(nodes.len());
+        let mut halted = None;
+
+        let mut batches = nodes.into_iter().peekable();
+        let mut batch_number = 0;
+        while batches.peek().is_some() {
+            let batch = batches.by_ref().take(batch_size).collect::<Vec<_>>();
+            batch_number += 1;
+
+            if halted.is_some() {
+                executions.extend(batch.into_iter().map(|(role, node)| NodeExecution {
+                    role,
+                    node_id: node.id().clone(),
+                    outcome: ExecOutcome::Skipped,
+                    elapsed: Duration::ZERO,
+                }));
+                continue;
+            }
+
+            if batch_count > 1 {
+                info!("starting batch {batch_number}/{batch_count}");
+            }
+            let gate_nodes = batch.clone();
+            let batch_executions = self.run_batch(batch, &task).await;
+            let failed = batch_executions.iter().filter(|e| !e.succeeded()).count();
+            executions.extend(batch_executions);
+
+            if failed > 0 {
+                // failures of the last batch are reported by its executions.
+                if batches.peek().is_some() {
+                    halted = Some(anyhow::anyhow!(
+                        "{failed} nodes failed in batch {batch_number}"
+                    ));
+                }
+                continue;
+            }
+            if let Some(gate) = self.health_gate.as_ref() {
+                if let Err(err) = gate(gate_nodes).await {
+                    warn!("health gate failed after batch {batch_number}: {err:#}");
+                    halted = Some(err.context(format!("health gate after batch {batch_number}")));
+                    continue;
+                }
+            }
+            if batches.peek().is_some() && !self.strategy.batch_pause.is_zero() {
+                info!("pausing {:?} before next batch", self.strategy.batch_pause);
+                tokio::time::sleep(self.strategy.batch_pause).await;
+            }
+        }
+
+        RolloutReport { executions, halted }
+    }
+
+    async fn run_batch<F, Fut, R>(
+        &self,
+        batch: Vec<(NodeRole, N)>,
+        task: &F,
+    ) -> Vec<NodeExecution<R>>
+    where
+        F: Fn(NodeRole, N) -> Fut,
+        Fut: Future<Output = anyhow::Result<R>> + Send + 'static,
+        R: TaskOutput,
+    {
+        let permits = Arc::new(Semaphore::new(
+            self.strategy.parallel.unwrap_or(batch.len()).max(1),
+        ));
+
+        let mut aborts = Vec::with_capacity(batch.len());
+        let mut handles = FuturesUnordered::new();
+        for (index, (role, node)) in batch.into_iter().enumerate() {
+            let node_id = node.id().clone();
+            let permits = Arc::clone(&permits);
+            let run = task(role, node);
+            let (run, abort) = abortable(async move {
+                let _permit = permits.acquire_owned().await;
+                let started = Instant::now();
+                let result = run.await;
+                (result, started.elapsed())
+            });
+            aborts.push(abort);
+            handles.push(tokio::spawn(run).map(move |joined| (index, role, node_id, joined)));
+        }
+
+        let mut executions = Vec::with_capacity(handles.len());
+        while let Some((index, role, node_id, joined)) = handles.next().await {
+            let (outcome, elapsed) = match joined {
+                Ok(Ok((Ok(output), elapsed))) => (ExecOutcome::Completed(output), elapsed),
+                Ok(Ok((Err(err), elapsed))) => (ExecOutcome::Error(err), elapsed),
+                Ok(Err(Aborted)) => (ExecOutcome::Cancelled, Duration::ZERO),
+                Err(err) => (ExecOutcome::Error(err.into()), Duration::ZERO),
+            };
+            let execution = NodeExecution {
+                role,
+                node_id,
+                outcome,
+                elapsed,
+            };
+            if self.strategy.fail_fast && !execution.succeeded() {
+                aborts.iter().for_each(AbortHandle::abort);
+            }
+            executions.push((index, execution));
+        }
+        executions.sort_by_key(|(index, _)| *index);
+
+        executions
+            .into_iter()
+            .map(|(_, execution)| execution)
+            .collect()
+    }
+}
@@ -1,6 +1,87 @@
-pub async fn connect(user: &str, host: &str) -> anyhow::Result<openssh::Session> {
-    let session =
-        openssh::Session::connect(&format!("{user}@{host}"), openssh::KnownHosts::Accept).await?;
+mod pool;
+pub use pool::SessionPool;
+
+use std::{io::Write, os::unix::process::CommandExt};
+
+use tempfile::NamedTempFile;
+
+use crate::config::SshConfig;
+
+pub async fn connect(ssh_config: &SshConfig, host: &str) -> anyhow::Result<openssh::Session> {
+    let mut builder = openssh::SessionBuilder::default();
+    builder
+        .user(ssh_config.user.clone())
+        .known_hosts_check(openssh::KnownHosts::Accept);
+    if let Some(identity_file) = ssh_config.identity_file.as_ref() {
+        builder.keyfile(identity_file);
+    }
+    // SessionBuilder does not support jump hosts, so pass them through a generated config file.
+    // ssh reads it only when the session starts, so it is removed once connected.
+    let config_file = match ssh_config.bastion.as_ref() {
+        Some(bastion) => Some(write_config_file(ssh_config, bastion)?),
+        None => None,
+    };
+    if let Some(config_file) = config_file.as_ref() {
+        builder.config_file(config_file.path());
+    }
+
+    let session = builder.connect(host).await?;
 
     Ok(session)
 }
+
+/// Replace current process with an interactive ssh to host.
+/// Returns only if ssh could not be executed.
+pub fn interactive(ssh_config: &SshConfig, host: &str) -> anyhow::Error {
+    let mut command = std::process::Command::new("ssh");
+    for (key, value) in options(ssh_config) {
+        command.arg("-o").arg(format!("{key}={value}"));
+    }
+    command.arg(format!("{}@{host}", ssh_config.user));
+
+    anyhow::Error::from(command.exec())
+}
+
+/// ssh_config(5) options derived from the configuration.
+pub fn options(ssh_config: &SshConfig) -> Vec<(&'static str, String)> {
+    let mut options = Vec::new();
+    if let Some(identity_file) = ssh_config.identity_file.as_ref() {
+        options.push(("IdentityFile", identity_file.display().to_string()));
+        options.push(("IdentitiesOnly", "yes".to_owned()));
+    }
+    if let Some(bastion) = ssh_config.bastion.as_ref() {
+        options.push(("ProxyJump", bastion.clone()));
+    }
+    options
+}
+
+/// Write the options to a temporary file readable only by the user.
+fn write_config_file(ssh_config: &SshConfig, bastion: &str) -> anyhow::Result<NamedTempFile> {
+    // ssh connects to the bastion with the same config file, which must not jump to itself.
+    let bastion_host = bastion_host(bastion);
+    let mut content = String::new();
+    content.push_str(&format!("Host * !{bastion_host}\n"));
+    content.push_str(&format!("    ProxyJump {bastion}\n"));
+    content.push_str("Host *\n");
+    for (key, value) in options(ssh_config) {
+        if key != "ProxyJump" {
+            content.push_str(&format!("    {key} {value}\n"));
+        }
+    }
+    // keep user settings such as the identity for the bastion itself.
+    content.push_str("    Include ~/.ssh/config\n");
+
+    let mut file = tempfile::Builder::new()
+        .prefix("kubeprovision-")
+        .suffix(".ssh_config")
+        .tempfile()?;
+    file.write_all(content.as_bytes())?;
+
+    Ok(file)
+}
+
+/// Host part of `[user@]host[:port]`.
+fn bastion_host(bastion: &str) -> &str {
+    let host = bastion.rsplit_once('@').map_or(bastion, |(_, host)| host);
+    host.split_once(':').map_or(host, |(host, _)| host)
+}
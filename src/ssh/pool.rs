@@ -0,0 +1,65 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::anyhow;
+use openssh::Session;
+use tokio::sync::Mutex as AsyncMutex;
+use tracing::{debug, info};
+
+use crate::{
+    config::SshConfig,
+    node::{Node, NodeId},
+    ssh,
+};
+
+type Slot = Arc<AsyncMutex<Option<Arc<Session>>>>;
+
+/// One ssh session per node, reused across the steps of a usecase.
+pub struct SessionPool {
+    ssh_config: SshConfig,
+    slots: Mutex<HashMap<NodeId, Slot>>,
+}
+
+impl SessionPool {
+    pub fn new(ssh_config: SshConfig) -> Self {
+        Self {
+            ssh_config,
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Return the session for given node.
+    /// Cached session is health checked before reuse and reconnected if it is broken.
+    pub async fn session(&self, node: &impl Node) -> anyhow::Result<Arc<Session>> {
+        let slot = self.slot(node.id());
+        // holding the slot lock while connecting prevents concurrent callers
+        // from opening duplicate sessions to the same node.
+        let mut slot = slot.lock().await;
+
+        if let Some(session) = slot.as_ref() {
+            match session.check().await {
+                Ok(_) => return Ok(Arc::clone(session)),
+                Err(err) => info!(node_id=%node.id(), "ssh session is broken, reconnecting: {err}"),
+            }
+        }
+
+        let public_ip = node.public_ip().ok_or_else(|| {
+            anyhow!(
+                "node {} does not have public ip. maybe not started",
+                node.id()
+            )
+        })?;
+        debug!(node_id=%node.id(), "connecting to {public_ip}");
+        let session = Arc::new(ssh::connect(&self.ssh_config, &public_ip.to_string()).await?);
+        *slot = Some(Arc::clone(&session));
+
+        Ok(session)
+    }
+
+    fn slot(&self, node_id: &NodeId) -> Slot {
+        let mut slots = self.slots.lock().unwrap();
+        Arc::clone(slots.entry(node_id.clone()).or_default())
+    }
+}
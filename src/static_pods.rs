@@ -0,0 +1,41 @@
+use crate::provision::{Command, RemoteCommandExecutor};
+
+// stop_static_pods fails if an interrupted run left manifests behind. move them back by hand.
+// the functions do not rely on errexit, which is ignored in traps and && lists.
+const STATIC_PODS_FUNCTIONS: &str = r#"set -euo pipefail
+crictl="crictl --runtime-endpoint unix:///run/containerd/containerd.sock"
+manifests=/etc/kubernetes/manifests
+stopped=/etc/kubernetes/manifests.kubeprovision
+stopped_pods=
+stop_static_pods() {
+    mkdir "$stopped" || return
+    stopped_pods=1
+    mv "$manifests"/*.yaml "$stopped/" || return
+    for _ in $(seq 60); do
+        test -z "$($crictl ps --name "$1" --quiet)" && return 0
+        sleep 2
+    done
+    echo "control plane containers did not stop" >&2
+    return 1
+}
+start_static_pods() {
+    if [ -n "$stopped_pods" ]; then
+        mv "$stopped"/*.yaml "$manifests/" && rmdir "$stopped" && stopped_pods=
+    fi
+}
+"#;
+
+/// Run script as root with the static pod functions defined. Returns the trimmed stdout.
+pub async fn run_script(
+    executor: &impl RemoteCommandExecutor,
+    name: &str,
+    script: &str,
+    args: &[&str],
+) -> anyhow::Result<String> {
+    let script = format!("{STATIC_PODS_FUNCTIONS}{script}");
+    let mut command = vec!["bash", "-c", &script, "kubeprovision"];
+    command.extend_from_slice(args);
+
+    let stdout = executor.stdout(Command::Sudo(&command), name).await?;
+    Ok(String::from_utf8_lossy(&stdout).trim().to_owned())
+}
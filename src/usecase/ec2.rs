@@ -1,14 +1,40 @@
+use std::{
+    collections::HashSet,
+    process::Output,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
 use anyhow::anyhow;
+use aws_sdk_ec2::model::InstanceStateName;
+use futures::FutureExt;
 use tracing_futures::Instrument;
 
 use crate::{
-    config::SshConfig,
-    node::{ClusterNodes, Node, NodeRole, EC2},
+    certs::{Certificate, Certs},
+    config::{ClusterConfig, Ec2NodeConfig, LoadBalancerKind},
+    etcd::{Etcd, ETCD_DATA_DIR},
+    kubeconfig::Kubeconfig,
+    kubernetes::{KubeNode, Kubectl, ADMIN_KUBECONFIG, SUPER_ADMIN_KUBECONFIG},
+    node::{ClusterNodes, Node, NodeId, NodeRole, NodeSelector, NodeTarget, EC2},
     operator::AwsOperator,
-    provision::{Command, Provisioner, RemoteCommandExecutor},
-    ssh,
+    preflight::{self, Check, CheckStatus, NodeFacts, NodePreflight},
+    provision::{
+        Command, Distribution, Provisioner, ProxyEnv, RegistryHosts, RemoteCommandExecutor, Vars,
+    },
+    rollout::{HealthGate, Rollout, RolloutReport, RolloutStrategy},
+    ssh::SessionPool,
+    verify,
 };
 
+/// Scheduling change applied to kubernetes nodes by `schedule`.
+#[derive(Debug, Clone, Copy)]
+pub enum Schedule {
+    Drain { timeout: Duration },
+    Cordon,
+    Uncordon,
+}
+
 pub async fn collect(operator: &AwsOperator) -> anyhow::Result<ClusterNodes<EC2>> {
     operator.list_nodes().await
 }
@@ -29,80 +55,539 @@ pub async fn stop_instances(operator: &AwsOperator) -> anyhow::Result<ClusterNod
     Ok(cluster_nodes)
 }
 
-// TODO: Refactor
+/// Annotation on workers drained by `stop_instances_gracefully`, which are uncordoned on start.
+const DRAINED_BY_STOP: &str = "kubeprovision/drained-by-stop";
+
+/// Drain and stop workers, then stop masters once every worker has stopped.
+pub async fn stop_instances_gracefully(
+    sessions: &SessionPool,
+    operator: &AwsOperator,
+    drain_timeout: Duration,
+    timeout: Duration,
+) -> anyhow::Result<ClusterNodes<EC2>> {
+    let cluster_nodes = collect(operator).await?;
+
+    // the cluster may be broken already. stop instances anyway.
+    let kube_nodes = async {
+        let kubectl = master_kubectl(sessions, &cluster_nodes).await?;
+        let kube_nodes = kubectl.nodes().await?;
+        Ok::<_, anyhow::Error>((kubectl, kube_nodes))
+    }
+    .await;
+    match kube_nodes {
+        Ok((kubectl, kube_nodes)) => {
+            for worker in cluster_nodes.worker.iter() {
+                let kube_node = match find_kube_node(&kube_nodes, worker) {
+                    Some(kube_node) => kube_node,
+                    None => continue,
+                };
+                // nodes cordoned by hand stay cordoned on start.
+                if !kube_node.unschedulable {
+                    kubectl
+                        .annotate(&kube_node.name, &[format!("{DRAINED_BY_STOP}=true")])
+                        .await?;
+                }
+                tracing::info!("draining {} ({})", kube_node.name, worker.id());
+                kubectl.drain(&kube_node.name, drain_timeout).await?;
+            }
+        }
+        Err(err) => tracing::warn!("Could not get kubernetes nodes, skip draining: {err:#}"),
+    }
+
+    let workers = ClusterNodes {
+        master: Vec::new(),
+        worker: cluster_nodes.worker.clone(),
+    };
+    tracing::info!(
+        "stopping workers {:?}",
+        workers.node_ids().collect::<Vec<_>>()
+    );
+    operator.stop_nodes(&workers).await?;
+    wait_instances(
+        operator,
+        NodeRole::Worker,
+        InstanceStateName::Stopped,
+        timeout,
+    )
+    .await?;
+
+    let masters = ClusterNodes {
+        master: cluster_nodes.master.clone(),
+        worker: Vec::new(),
+    };
+    tracing::info!(
+        "stopping masters {:?}",
+        masters.node_ids().collect::<Vec<_>>()
+    );
+    operator.stop_nodes(&masters).await?;
+    wait_instances(
+        operator,
+        NodeRole::Master,
+        InstanceStateName::Stopped,
+        timeout,
+    )
+    .await?;
+
+    Ok(cluster_nodes)
+}
+
+/// Start masters, then workers once the api server responds. Uncordon workers drained by stop.
+pub async fn start_instances_gracefully(
+    sessions: &Arc<SessionPool>,
+    operator: &AwsOperator,
+    timeout: Duration,
+) -> anyhow::Result<ClusterNodes<EC2>> {
+    let cluster_nodes = collect(operator).await?;
+
+    let masters = ClusterNodes {
+        master: cluster_nodes.master.clone(),
+        worker: Vec::new(),
+    };
+    tracing::info!(
+        "starting masters {:?}",
+        masters.node_ids().collect::<Vec<_>>()
+    );
+    operator.start_nodes(&masters).await?;
+    // public ips change on start.
+    let cluster_nodes = wait_instances(
+        operator,
+        NodeRole::Master,
+        InstanceStateName::Running,
+        timeout,
+    )
+    .await?;
+
+    wait_api_server(sessions, &cluster_nodes, timeout).await?;
+
+    let workers = ClusterNodes {
+        master: Vec::new(),
+        worker: cluster_nodes.worker.clone(),
+    };
+    tracing::info!(
+        "starting workers {:?}",
+        workers.node_ids().collect::<Vec<_>>()
+    );
+    operator.start_nodes(&workers).await?;
+    let cluster_nodes = wait_instances(
+        operator,
+        NodeRole::Worker,
+        InstanceStateName::Running,
+        timeout,
+    )
+    .await?;
+
+    let master = cluster_nodes
+        .master
+        .first()
+        .cloned()
+        .ok_or_else(|| anyhow!("master node not found"))?;
+    let gate = ready_health_gate(Arc::clone(sessions), master, None, timeout);
+    gate(cluster_nodes.clone().into_nodes().collect()).await?;
+
+    let kubectl = master_kubectl(sessions, &cluster_nodes).await?;
+    let kube_nodes = kubectl.nodes().await?;
+    for worker in cluster_nodes.worker.iter() {
+        let kube_node = match find_kube_node(&kube_nodes, worker) {
+            Some(kube_node) if kube_node.annotations.contains_key(DRAINED_BY_STOP) => kube_node,
+            _ => continue,
+        };
+        kubectl.uncordon(&kube_node.name).await?;
+        kubectl
+            .annotate(&kube_node.name, &[format!("{DRAINED_BY_STOP}-")])
+            .await?;
+    }
+
+    Ok(cluster_nodes)
+}
+
+/// Poll until kubectl on a master gets nodes from the api server.
+async fn wait_api_server(
+    sessions: &SessionPool,
+    cluster_nodes: &ClusterNodes<EC2>,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    tracing::info!("waiting for the api server");
+    let started = Instant::now();
+    loop {
+        match kubernetes_nodes(sessions, cluster_nodes).await {
+            Ok(_) => return Ok(()),
+            Err(err) if started.elapsed() >= timeout => {
+                return Err(err.context("api server did not respond"));
+            }
+            Err(err) => tracing::debug!("api server is not ready: {err:#}"),
+        }
+        tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+    }
+}
+
+/// Poll instances until every node of the role is in the state.
+async fn wait_instances(
+    operator: &AwsOperator,
+    role: NodeRole,
+    state: InstanceStateName,
+    timeout: Duration,
+) -> anyhow::Result<ClusterNodes<EC2>> {
+    let started = Instant::now();
+    loop {
+        let cluster_nodes = collect(operator).await?;
+        let pending = cluster_nodes
+            .nodes()
+            .filter(|(r, node)| *r == role && node.state() != state)
+            .map(|(_, node)| node.id().to_string())
+            .collect::<Vec<_>>();
+        if pending.is_empty() {
+            return Ok(cluster_nodes);
+        }
+        if started.elapsed() >= timeout {
+            return Err(anyhow!(
+                "{role} nodes did not become {} within {}s: {}",
+                state.as_str(),
+                timeout.as_secs(),
+                pending.join(", ")
+            ));
+        }
+        tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+    }
+}
+
+/// Execute command on each node following the rollout strategy.
 pub async fn exec(
-    ssh_config: SshConfig,
+    sessions: Arc<SessionPool>,
     operator: AwsOperator,
     command: String,
-    role: Option<NodeRole>,
-) -> anyhow::Result<()> {
-    let cluster_nodes = collect(&operator).await?;
-    // Create methods
-    let nodes = match role {
-        Some(role) => match role {
-            NodeRole::Master => cluster_nodes.master,
-            NodeRole::Worker => cluster_nodes.worker,
-        },
-        None => cluster_nodes.into_nodes().map(|(_, node)| node).collect(),
+    selector: &NodeSelector,
+    rollout: Rollout<EC2>,
+) -> anyhow::Result<RolloutReport<Output>> {
+    let nodes = collect(&operator).await?.select(selector);
+
+    let report = rollout
+        .run(nodes.into_nodes().collect(), |_, node| {
+            exec_node(Arc::clone(&sessions), node, command.clone())
+        })
+        .await;
+
+    Ok(report)
+}
+
+async fn exec_node(
+    sessions: Arc<SessionPool>,
+    node: EC2,
+    command: String,
+) -> anyhow::Result<Output> {
+    async {
+        let session = sessions.session(&node).await?;
+        let output = session.output(Command::Bash(&command)).await?;
+        Ok(output)
+    }
+    .instrument(tracing::info_span!("exec", node_id=%node.id()))
+    .await
+}
+
+/// Health gate which runs command on every node of the batch until it succeeds or timeout.
+pub fn command_health_gate(
+    sessions: Arc<SessionPool>,
+    command: String,
+    timeout: Duration,
+) -> HealthGate<EC2> {
+    Box::new(move |nodes| {
+        let sessions = Arc::clone(&sessions);
+        let command = command.clone();
+
+        async move {
+            let started = Instant::now();
+            for (_, node) in nodes {
+                loop {
+                    let output =
+                        exec_node(Arc::clone(&sessions), node.clone(), command.clone()).await;
+                    // the node may not accept ssh yet while it restarts.
+                    let reason = match output {
+                        Ok(output) if output.status.success() => break,
+                        Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+                        Err(err) => format!("{err:#}"),
+                    };
+                    if started.elapsed() >= timeout {
+                        return Err(anyhow!(
+                            "health check failed on node {}: {reason}",
+                            node.id()
+                        ));
+                    }
+                    tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+                }
+            }
+            Ok(())
+        }
+        .boxed()
+    })
+}
+
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Health gate which waits until every node of the batch is Ready in kubernetes.
+/// If version is given, kubelet of the nodes must report that minor version as well.
+pub fn ready_health_gate(
+    sessions: Arc<SessionPool>,
+    master: EC2,
+    version: Option<String>,
+    timeout: Duration,
+) -> HealthGate<EC2> {
+    Box::new(move |nodes| {
+        let sessions = Arc::clone(&sessions);
+        let master = master.clone();
+        let version_prefix = version.as_ref().map(|version| format!("v{version}."));
+
+        async move {
+            let started = Instant::now();
+            let kubectl = Kubectl::new(sessions.session(&master).await?);
+            loop {
+                let kube_nodes = kubectl.nodes().await?;
+                let not_ready = nodes
+                    .iter()
+                    .filter(|(_, node)| match find_kube_node(&kube_nodes, node) {
+                        Some(kube_node) => {
+                            let upgraded = match version_prefix.as_ref() {
+                                Some(prefix) => kube_node.kubelet_version.starts_with(prefix),
+                                None => true,
+                            };
+                            !kube_node.is_ready() || !upgraded
+                        }
+                        None => true,
+                    })
+                    .map(|(_, node)| node.id().to_string())
+                    .collect::<Vec<_>>();
+                if not_ready.is_empty() {
+                    return Ok(());
+                }
+                if started.elapsed() >= timeout {
+                    return Err(anyhow!("nodes are not ready: {}", not_ready.join(", ")));
+                }
+                tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+            }
+        }
+        .boxed()
+    })
+}
+
+/// Fetch admin kubeconfig from the first master and convert it for use from local.
+pub async fn fetch_kubeconfig(
+    cluster: &ClusterConfig,
+    sessions: &SessionPool,
+    operator: &AwsOperator,
+) -> anyhow::Result<Kubeconfig> {
+    let cluster_nodes = collect(operator).await?;
+    let master = cluster_nodes
+        .master
+        .first()
+        .ok_or_else(|| anyhow!("master node not found"))?;
+    let public_ip = master.public_ip().ok_or_else(|| {
+        anyhow!(
+            "node {} does not have public ip. maybe not started",
+            master.id()
+        )
+    })?;
+
+    let session = sessions.session(master).await?;
+    let admin_conf = session.fetch(ADMIN_KUBECONFIG).await?;
+
+    Kubeconfig::from_slice(&admin_conf)?.for_cluster(&cluster.name, public_ip)
+}
+
+/// Kubernetes nodes seen from the first reachable master.
+pub async fn kubernetes_nodes(
+    sessions: &SessionPool,
+    cluster_nodes: &ClusterNodes<EC2>,
+) -> anyhow::Result<Vec<KubeNode>> {
+    master_kubectl(sessions, cluster_nodes).await?.nodes().await
+}
+
+/// kubectl on the first reachable master.
+async fn master_kubectl(
+    sessions: &SessionPool,
+    cluster_nodes: &ClusterNodes<EC2>,
+) -> anyhow::Result<Kubectl<Arc<openssh::Session>>> {
+    let mut last_err = anyhow!("master node not found");
+    for master in cluster_nodes.master.iter() {
+        match sessions.session(master).await {
+            Ok(session) => return Ok(Kubectl::new(session)),
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+/// Drain, cordon or uncordon the kubernetes nodes of instances matching the target.
+/// Returns the kubernetes node name of each instance in the order they were processed.
+pub async fn schedule(
+    sessions: &SessionPool,
+    operator: &AwsOperator,
+    target: &NodeTarget,
+    schedule: Schedule,
+) -> anyhow::Result<Vec<(NodeId, String)>> {
+    let cluster_nodes = collect(operator).await?;
+    let instances = cluster_nodes.find(target);
+    if instances.is_empty() {
+        return Err(anyhow!("node {target} not found"));
+    }
+
+    let kubectl = master_kubectl(sessions, &cluster_nodes).await?;
+    let kube_nodes = kubectl.nodes().await?;
+
+    let mut scheduled = Vec::with_capacity(instances.len());
+    for (_, instance) in instances {
+        let node_name = find_kube_node(&kube_nodes, instance)
+            .map(|n| n.name.clone())
+            .ok_or_else(|| anyhow!("node {} has not joined the cluster", instance.id()))?;
+        match schedule {
+            Schedule::Drain { timeout } => kubectl.drain(&node_name, timeout).await?,
+            Schedule::Cordon => kubectl.cordon(&node_name).await?,
+            Schedule::Uncordon => kubectl.uncordon(&node_name).await?,
+        }
+        scheduled.push((instance.id().clone(), node_name));
+    }
+
+    Ok(scheduled)
+}
+
+/// Find the kubernetes node of the instance by private ip or hostname.
+pub fn find_kube_node<'a>(kube_nodes: &'a [KubeNode], instance: &EC2) -> Option<&'a KubeNode> {
+    let by_ip = instance
+        .private_ip()
+        .and_then(|ip| kube_nodes.iter().find(|n| n.internal_ip == Some(ip)));
+    // node name is either the private dns name or its first label, depending on the kubelet setting.
+    let by_hostname = || {
+        let dns_name = instance.private_dns_name()?;
+        let short_name = dns_name.split('.').next().unwrap_or(dns_name);
+        kube_nodes.iter().find(|n| {
+            let hostname = n.hostname.as_deref().unwrap_or(&n.name);
+            [n.name.as_str(), hostname]
+                .iter()
+                .any(|name| *name == dns_name || *name == short_name)
+        })
     };
 
+    by_ip.or_else(by_hostname)
+}
+
+/// Fetch the file at path from each node.
+pub async fn fetch_files(
+    sessions: Arc<SessionPool>,
+    operator: &AwsOperator,
+    path: String,
+    selector: &NodeSelector,
+) -> anyhow::Result<Vec<(NodeId, Vec<u8>)>> {
+    let nodes = collect(operator).await?.select(selector);
+
     let mut handles = Vec::with_capacity(nodes.len());
-    for node in nodes {
-        let public_ip = node.public_ip().ok_or_else(|| {
-            anyhow!(
-                "node {} does not have public ip. maybe not started",
-                node.id()
-            )
-        })?;
-        let ssh_user = ssh_config.user.clone();
-        let command = command.clone();
+    for (_, node) in nodes.into_nodes() {
+        let sessions = Arc::clone(&sessions);
+        let path = path.clone();
 
         let handle = tokio::spawn(async move {
-            let session = ssh::connect(&ssh_user, &public_ip.to_string())
-                .await
-                .expect("should connect");
-
-            session
-                .execute(Command::Bash(&command))
-                .await
-                .expect("command failed");
+            let session = sessions.session(&node).await?;
+            let content = session.fetch(&path).await?;
+            Ok::<_, anyhow::Error>((node.id().clone(), content))
         });
         handles.push(handle);
     }
 
+    let mut files = Vec::with_capacity(handles.len());
     for handle in handles {
-        handle.await?;
+        files.push(handle.await??);
     }
 
-    Ok(())
+    Ok(files)
 }
 
-pub async fn provision(ssh_config: &SshConfig, operator: &AwsOperator) -> anyhow::Result<()> {
-    // TODO: make sure all nodes started.
-    let cluster_nodes = collect(operator).await?;
-    let mut provision_handles = Vec::with_capacity(cluster_nodes.len());
+/// Evaluate preflight checks of selected nodes, looking for duplicates across the whole cluster.
+pub async fn preflight(
+    sessions: &Arc<SessionPool>,
+    operator: &AwsOperator,
+    selector: &NodeSelector,
+) -> anyhow::Result<Vec<NodePreflight>> {
+    let nodes = collect(operator).await?;
+    let selected = nodes
+        .clone()
+        .select(selector)
+        .nodes()
+        .map(|(_, node)| node.id().clone())
+        .collect::<HashSet<_>>();
+
+    let mut handles = Vec::with_capacity(nodes.len());
+    for (role, node) in nodes.into_nodes() {
+        let sessions = Arc::clone(sessions);
 
-    for (role, node) in cluster_nodes.into_nodes() {
-        let handle = tokio::spawn(provision_node(ssh_config.user.clone(), role, node));
-        provision_handles.push(handle);
+        let handle = tokio::spawn(async move {
+            let facts = async {
+                let session = sessions.session(&node).await?;
+                NodeFacts::gather(&session).await
+            }
+            .instrument(tracing::info_span!("preflight", node_id=%node.id()))
+            .await;
+            (role, node.id().clone(), facts)
+        });
+        handles.push(handle);
     }
 
-    for provision in provision_handles.into_iter() {
-        provision.await??
+    let mut facts = Vec::with_capacity(handles.len());
+    for handle in handles {
+        facts.push(handle.await?);
     }
 
-    Ok(())
+    Ok(preflight::evaluate(facts)
+        .into_iter()
+        .filter(|result| selected.contains(&result.node_id))
+        .collect())
 }
 
-async fn provision_node(ssh_user: String, role: NodeRole, node: impl Node) -> anyhow::Result<()> {
-    let public_ip = node.public_ip().ok_or_else(|| {
-        anyhow!(
-            "node {} does not have public ip. maybe not started",
-            node.id()
-        )
-    })?;
-    let session = ssh::connect(&ssh_user, &public_ip.to_string()).await?;
-    let provisioner = Provisioner::new(session);
+pub async fn provision(
+    cluster: &ClusterConfig,
+    distribution: Option<Distribution>,
+    sessions: &Arc<SessionPool>,
+    operator: &AwsOperator,
+    selector: &NodeSelector,
+    rollout: Rollout<EC2>,
+) -> anyhow::Result<RolloutReport<()>> {
+    let registries = cluster
+        .registries
+        .iter()
+        .map(RegistryHosts::from_config)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    // TODO: make sure all nodes started.
+    let cluster_nodes = collect(operator).await?;
+    let proxy = cluster_proxy(cluster, &cluster_nodes);
+    let cluster_nodes = cluster_nodes.select(selector);
+
+    let report = rollout
+        .run(cluster_nodes.into_nodes().collect(), |role, node| {
+            let vars = Vars::default().cluster(cluster).node(role, &node);
+            provision_node(
+                Arc::clone(sessions),
+                vars,
+                distribution,
+                registries.clone(),
+                proxy.clone(),
+                role,
+                node,
+            )
+        })
+        .await;
+
+    Ok(report)
+}
+
+async fn provision_node(
+    sessions: Arc<SessionPool>,
+    vars: Vars,
+    distribution: Option<Distribution>,
+    registries: Vec<RegistryHosts>,
+    proxy: Option<ProxyEnv>,
+    role: NodeRole,
+    node: impl Node,
+) -> anyhow::Result<()> {
+    let session = sessions.session(&node).await?;
+    let provisioner = Provisioner::new(session, vars)
+        .distribution(distribution)
+        .registries(registries)
+        .proxy(proxy);
     provisioner
         .provision()
         .instrument(tracing::info_span!(
@@ -113,3 +598,547 @@ async fn provision_node(ssh_user: String, role: NodeRole, node: impl Node) -> an
         .await
         .map_err(anyhow::Error::from)
 }
+
+/// Proxy environment of nodes if the cluster uses a proxy.
+fn cluster_proxy(cluster: &ClusterConfig, cluster_nodes: &ClusterNodes<EC2>) -> Option<ProxyEnv> {
+    let proxy = cluster.proxy.as_ref()?;
+    let node_ips = cluster_nodes
+        .nodes()
+        .filter_map(|(_, node)| node.private_ip())
+        .collect::<Vec<_>>();
+    Some(ProxyEnv::new(proxy, cluster, &node_ips))
+}
+
+/// Initialize the control plane on the first master, then join selected masters and workers.
+/// Nodes which are already part of the cluster are left as is.
+pub async fn bootstrap(
+    cluster: &ClusterConfig,
+    node_config: &Ec2NodeConfig,
+    sessions: &SessionPool,
+    operator: &AwsOperator,
+    selector: &NodeSelector,
+) -> anyhow::Result<()> {
+    let cluster_nodes = collect(operator).await?;
+    let master = cluster_nodes
+        .master
+        .first()
+        .ok_or_else(|| anyhow!("master node not found"))?;
+    if cluster_nodes.master.len() > 1 && cluster.control_plane_endpoint().is_none() {
+        return Err(anyhow!(
+            "cluster.controlPlaneEndpoint or cluster.loadBalancer is required for {} masters",
+            cluster_nodes.master.len()
+        ));
+    }
+
+    // masters run the load balancer in front of the api servers.
+    let servers = cluster_nodes
+        .master
+        .iter()
+        .filter_map(|node| node.private_ip())
+        .collect::<Vec<_>>();
+    let master_vars = |index: usize, node: &EC2, kubeconfig: &str| {
+        let vars = Vars::default()
+            .cluster(cluster)
+            .node(NodeRole::Master, node)
+            .taints(
+                NodeRole::Master,
+                &node_config.node_labels(NodeRole::Master, node).taints,
+            );
+        match cluster.load_balancer.as_ref() {
+            // the first master holds the virtual ip so that kubeadm init can reach the endpoint.
+            Some(lb) => {
+                vars.load_balancer(lb, &servers, if index == 0 { 101 } else { 100 }, kubeconfig)
+            }
+            None => vars,
+        }
+    };
+    let lb_kind = cluster.load_balancer.as_ref().map(|lb| lb.kind);
+
+    let provisioner = Provisioner::new(
+        sessions.session(master).await?,
+        master_vars(0, master, init_kubeconfig(cluster)),
+    )
+    .proxy(cluster_proxy(cluster, &cluster_nodes));
+    async {
+        if let Some(kind) = lb_kind {
+            provisioner.deploy_load_balancer(kind).await?;
+        }
+        provisioner
+            .init_control_plane(&cluster.cni_manifest)
+            .await?;
+        if lb_kind == Some(LoadBalancerKind::KubeVip) {
+            // switch kube-vip from super-admin.conf to admin.conf once it is authorized.
+            Provisioner::new(
+                sessions.session(master).await?,
+                master_vars(0, master, ADMIN_KUBECONFIG),
+            )
+            .deploy_load_balancer(LoadBalancerKind::KubeVip)
+            .await?;
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .instrument(tracing::info_span!("init", node_id=%master.id()))
+    .await?;
+    let join_token = provisioner.join_token().await?;
+    let mut certificate_key = None;
+
+    // masters join one at a time so that etcd membership changes do not overlap.
+    for (role, node) in cluster_nodes.clone().select(selector).into_nodes() {
+        if node.id() == master.id() {
+            continue;
+        }
+        match role {
+            NodeRole::Master => {
+                if certificate_key.is_none() {
+                    certificate_key = Some(provisioner.certificate_key().await?);
+                }
+                let index = cluster_nodes
+                    .master
+                    .iter()
+                    .position(|m| m.id() == node.id())
+                    .unwrap_or_default();
+                let joining = Provisioner::new(
+                    sessions.session(&node).await?,
+                    master_vars(index, &node, ADMIN_KUBECONFIG),
+                );
+                async {
+                    joining
+                        .join_control_plane(&join_token, certificate_key.as_deref().unwrap())
+                        .await?;
+                    // admin.conf exists only after joining.
+                    if let Some(kind) = lb_kind {
+                        joining.deploy_load_balancer(kind).await?;
+                    }
+                    Ok::<_, anyhow::Error>(())
+                }
+                .instrument(tracing::info_span!("join", role=%role, node_id=%node.id()))
+                .await?;
+            }
+            NodeRole::Worker => {
+                let vars = Vars::default()
+                    .cluster(cluster)
+                    .node(role, &node)
+                    .taints(role, &node_config.node_labels(role, &node).taints);
+                Provisioner::new(sessions.session(&node).await?, vars)
+                    .join(&join_token)
+                    .instrument(tracing::info_span!("join", role=%role, node_id=%node.id()))
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply configured labels and taints to the selected nodes. Removed ones are left as is.
+pub async fn label_nodes(
+    node_config: &Ec2NodeConfig,
+    sessions: &SessionPool,
+    operator: &AwsOperator,
+    selector: &NodeSelector,
+) -> anyhow::Result<()> {
+    let cluster_nodes = collect(operator).await?;
+    let kubectl = master_kubectl(sessions, &cluster_nodes).await?;
+    let kube_nodes = kubectl.nodes().await?;
+
+    for (role, node) in cluster_nodes.clone().select(selector).nodes() {
+        let node_labels = node_config.node_labels(role, node);
+        if node_labels.is_empty() {
+            continue;
+        }
+        let kube_node = match find_kube_node(&kube_nodes, node) {
+            Some(kube_node) => kube_node,
+            None => {
+                tracing::warn!("node {} has not joined the cluster, skip labels", node.id());
+                continue;
+            }
+        };
+
+        if !node_labels.labels.is_empty() {
+            kubectl.label(&kube_node.name, &node_labels.labels).await?;
+        }
+        if !node_labels.taints.is_empty() {
+            let taints = node_labels
+                .taints
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            kubectl.taint(&kube_node.name, &taints).await?;
+        }
+        tracing::info!(
+            "labeled {} ({}) with {} labels and {} taints",
+            kube_node.name,
+            node.id(),
+            node_labels.labels.len(),
+            node_labels.taints.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// kubeconfig kube-vip uses on the first master during kubeadm init.
+fn init_kubeconfig(cluster: &ClusterConfig) -> &'static str {
+    let minor = cluster
+        .kubernetes_version
+        .split('.')
+        .nth(1)
+        .and_then(|minor| minor.parse::<u32>().ok())
+        .unwrap_or(u32::MAX);
+    if minor >= 29 {
+        SUPER_ADMIN_KUBECONFIG
+    } else {
+        ADMIN_KUBECONFIG
+    }
+}
+
+/// Check that every node is Ready, system pods are running and
+/// pods on workers can reach each other and resolve names.
+pub async fn verify(
+    sessions: &SessionPool,
+    operator: &AwsOperator,
+    timeout: Duration,
+) -> anyhow::Result<Vec<Check>> {
+    let deadline = Instant::now() + timeout;
+    let cluster_nodes = collect(operator).await?;
+    let kubectl = master_kubectl(sessions, &cluster_nodes).await?;
+
+    let (mut checks, worker_names) = loop {
+        // the api server may be restarting right after bootstrap.
+        let kube_nodes = match kubectl.nodes().await {
+            Ok(kube_nodes) => kube_nodes,
+            Err(err) if Instant::now() >= deadline => {
+                let check = Check::new("node-ready", CheckStatus::Fail, format!("{err:#}"));
+                break (vec![check], Vec::new());
+            }
+            Err(err) => {
+                tracing::warn!("Could not list nodes: {err:#}");
+                tokio::time::sleep(verify::POLL_INTERVAL).await;
+                continue;
+            }
+        };
+        let mut worker_names = Vec::new();
+        let checks = cluster_nodes
+            .nodes()
+            .map(|(role, node)| match find_kube_node(&kube_nodes, node) {
+                Some(kube_node) if kube_node.is_ready() => {
+                    if role == NodeRole::Worker {
+                        worker_names.push(kube_node.name.clone());
+                    }
+                    Check::new(
+                        "node-ready",
+                        CheckStatus::Pass,
+                        format!("{} ({})", kube_node.name, node.id()),
+                    )
+                }
+                Some(kube_node) => Check::new(
+                    "node-ready",
+                    CheckStatus::Fail,
+                    format!("{} ({}) is {}", kube_node.name, node.id(), kube_node.ready),
+                ),
+                None => Check::new(
+                    "node-ready",
+                    CheckStatus::Fail,
+                    format!("{} has not joined", node.id()),
+                ),
+            })
+            .collect::<Vec<_>>();
+
+        let ready = checks.iter().all(|c| c.status == CheckStatus::Pass);
+        if ready || Instant::now() >= deadline {
+            break (checks, worker_names);
+        }
+        tokio::time::sleep(verify::POLL_INTERVAL).await;
+    };
+    if checks.iter().any(|c| c.status == CheckStatus::Fail) {
+        return Ok(checks);
+    }
+
+    checks.push(verify::system_pods(&kubectl, deadline).await);
+    checks.extend(verify::workloads(&kubectl, &worker_names, deadline).await);
+
+    Ok(checks)
+}
+
+/// Drain and delete selected workers, then kubeadm reset workers and masters in that order.
+pub async fn reset(
+    sessions: &Arc<SessionPool>,
+    operator: &AwsOperator,
+    selector: &NodeSelector,
+    purge: bool,
+    drain_timeout: Duration,
+) -> anyhow::Result<RolloutReport<()>> {
+    let cluster_nodes = collect(operator).await?;
+    let selected = cluster_nodes.clone().select(selector);
+
+    // the cluster may be broken or gone already. reset nodes anyway.
+    let kube_nodes = async {
+        let kubectl = master_kubectl(sessions, &cluster_nodes).await?;
+        let kube_nodes = kubectl.nodes().await?;
+        Ok::<_, anyhow::Error>((kubectl, kube_nodes))
+    }
+    .await;
+    match kube_nodes {
+        Ok((kubectl, kube_nodes)) => {
+            for (_, worker) in selected
+                .nodes()
+                .filter(|(role, _)| *role == NodeRole::Worker)
+            {
+                let kube_node = match find_kube_node(&kube_nodes, worker) {
+                    Some(kube_node) => kube_node,
+                    None => continue,
+                };
+                tracing::info!("draining {} ({})", kube_node.name, worker.id());
+                if let Err(err) = kubectl.drain(&kube_node.name, drain_timeout).await {
+                    tracing::warn!("Could not drain {}: {err:#}", kube_node.name);
+                }
+                if let Err(err) = kubectl.delete_node(&kube_node.name).await {
+                    tracing::warn!("Could not delete node {}: {err:#}", kube_node.name);
+                }
+            }
+        }
+        Err(err) => tracing::warn!("Could not get kubernetes nodes, skip draining: {err:#}"),
+    }
+
+    let (masters, workers) = selected
+        .into_nodes()
+        .partition::<Vec<_>, _>(|(role, _)| *role == NodeRole::Master);
+    let rollout = Rollout::new(RolloutStrategy::default());
+    let reset_node = |role: NodeRole, node: EC2| {
+        let sessions = Arc::clone(sessions);
+        async move {
+            let session = sessions.session(&node).await?;
+            Provisioner::new(session, Vars::default())
+                .reset(purge)
+                .instrument(tracing::info_span!("reset", role=%role, node_id=%node.id()))
+                .await
+                .map_err(anyhow::Error::from)
+        }
+    };
+
+    let mut report = rollout.run(workers, reset_node).await;
+    let masters = rollout.run(masters, reset_node).await;
+    report.executions.extend(masters.executions);
+
+    Ok(report)
+}
+
+/// Upgrade nodes one at a time to `cluster.kubernetes_version`, the first master first.
+/// Each node must become Ready before the next one starts.
+pub async fn upgrade(
+    cluster: &ClusterConfig,
+    distribution: Option<Distribution>,
+    sessions: &Arc<SessionPool>,
+    operator: &AwsOperator,
+    drain_timeout: Duration,
+    health_timeout: Duration,
+) -> anyhow::Result<RolloutReport<()>> {
+    let cluster_nodes = collect(operator).await?;
+    let master = cluster_nodes
+        .master
+        .first()
+        .cloned()
+        .ok_or_else(|| anyhow!("master node not found"))?;
+    let kube_nodes = kubernetes_nodes(sessions, &cluster_nodes).await?;
+
+    // kubeadm upgrades one minor version at a time. The oldest kubelet is taken as the cluster
+    // version so that an interrupted upgrade can be run again.
+    let current = kube_nodes
+        .iter()
+        .filter_map(|kube_node| minor_version(&kube_node.kubelet_version))
+        .min()
+        .ok_or_else(|| anyhow!("could not determine the kubernetes version of the cluster"))?;
+    let target = minor_version(&cluster.kubernetes_version)
+        .ok_or_else(|| anyhow!("invalid kubernetes version {}", cluster.kubernetes_version))?;
+    if target != (current.0, current.1 + 1) {
+        return Err(anyhow!(
+            "cannot upgrade from {}.{} to {}.{}, only the next minor version is supported",
+            current.0,
+            current.1,
+            target.0,
+            target.1
+        ));
+    }
+
+    // the package repository of the new version is reached through the proxy.
+    let proxy = cluster_proxy(cluster, &cluster_nodes);
+
+    let rollout = Rollout::new(RolloutStrategy {
+        parallel: Some(1),
+        batch_size: Some(1),
+        ..RolloutStrategy::default()
+    })
+    .health_gate(ready_health_gate(
+        Arc::clone(sessions),
+        master.clone(),
+        Some(cluster.kubernetes_version.clone()),
+        health_timeout,
+    ));
+
+    let report = rollout
+        .run(cluster_nodes.into_nodes().collect(), |role, node| {
+            let vars = Vars::default().cluster(cluster).node(role, &node);
+            let first = node.id() == master.id();
+            let node_name = find_kube_node(&kube_nodes, &node).map(|n| n.name.clone());
+            let sessions = Arc::clone(sessions);
+            let master = master.clone();
+            let proxy = proxy.clone();
+            let span = tracing::info_span!("upgrade", role=%role, node_id=%node.id());
+
+            async move {
+                let node_name = node_name
+                    .ok_or_else(|| anyhow!("node {} has not joined the cluster", node.id()))?;
+                let provisioner = Provisioner::new(sessions.session(&node).await?, vars)
+                    .distribution(distribution)
+                    .proxy(proxy);
+                let kubectl = Kubectl::new(sessions.session(&master).await?);
+
+                provisioner.upgrade_kubeadm().await?;
+                if first {
+                    provisioner.upgrade_apply().await?;
+                } else {
+                    provisioner.upgrade_node().await?;
+                }
+                kubectl.drain(&node_name, drain_timeout).await?;
+                provisioner.upgrade_kubelet().await?;
+                kubectl.uncordon(&node_name).await?;
+
+                Ok(())
+            }
+            .instrument(span)
+        })
+        .await;
+
+    Ok(report)
+}
+
+/// Major and minor of a version like v1.32.4 or 1.33.
+fn minor_version(version: &str) -> Option<(u32, u32)> {
+    let mut numbers = version.trim_start_matches('v').split('.');
+    let major = numbers.next()?.parse().ok()?;
+    let minor = numbers.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Download an etcd snapshot of the first master. Returns its file name and content.
+pub async fn etcd_backup(
+    sessions: &SessionPool,
+    operator: &AwsOperator,
+) -> anyhow::Result<(String, Vec<u8>)> {
+    let cluster_nodes = collect(operator).await?;
+    let master = cluster_nodes
+        .master
+        .first()
+        .ok_or_else(|| anyhow!("master node not found"))?;
+    let session = sessions.session(master).await?;
+
+    let path = Etcd::new(Arc::clone(&session))
+        .snapshot_save()
+        .instrument(tracing::info_span!("etcd", node_id=%master.id()))
+        .await?;
+    let content = session.fetch(&path).await;
+    session
+        .execute(Command::Sudo(&["rm", "-f", "--", &path]))
+        .await?;
+
+    let file_name = path.rsplit('/').next().unwrap_or(&path).to_owned();
+    Ok((file_name, content?))
+}
+
+/// Restore etcd of the master from the snapshot. Returns the previous data dir kept on the master.
+pub async fn etcd_restore(
+    sessions: &SessionPool,
+    operator: &AwsOperator,
+    snapshot: &[u8],
+    timeout: Duration,
+) -> anyhow::Result<String> {
+    let cluster_nodes = collect(operator).await?;
+    let master = match cluster_nodes.master.as_slice() {
+        [master] => master,
+        [] => return Err(anyhow!("master node not found")),
+        masters => {
+            return Err(anyhow!(
+                "restore supports a single master, but found {}. restore each etcd member by hand",
+                masters.len()
+            ))
+        }
+    };
+    let session = sessions.session(master).await?;
+
+    let path = format!("{ETCD_DATA_DIR}/kubeprovision-upload.db");
+    session
+        .execute(Command::PutFile {
+            path: &path,
+            content: snapshot,
+            mode: 0o600,
+            owner: "root:root",
+        })
+        .await?;
+    let previous = Etcd::new(session)
+        .snapshot_restore(&path)
+        .instrument(tracing::info_span!("etcd", node_id=%master.id()))
+        .await?;
+
+    wait_api_server(sessions, &cluster_nodes, timeout).await?;
+
+    Ok(previous)
+}
+
+/// Certificates of each master. Masters which could not be inspected have an error instead.
+pub async fn certificates(
+    sessions: &SessionPool,
+    operator: &AwsOperator,
+) -> anyhow::Result<Vec<(NodeId, anyhow::Result<Vec<Certificate>>)>> {
+    let cluster_nodes = collect(operator).await?;
+
+    let mut results = Vec::with_capacity(cluster_nodes.master.len());
+    for master in cluster_nodes.master.iter() {
+        let certificates = match sessions.session(master).await {
+            Ok(session) => Certs::new(session).check_expiration().await,
+            Err(err) => Err(err),
+        };
+        results.push((master.id().clone(), certificates));
+    }
+
+    Ok(results)
+}
+
+/// Renew certificates on masters one at a time, waiting for each api server to become live.
+pub async fn renew_certificates(
+    sessions: &Arc<SessionPool>,
+    operator: &AwsOperator,
+) -> anyhow::Result<RolloutReport<()>> {
+    let cluster_nodes = collect(operator).await?;
+    let masters = ClusterNodes {
+        master: cluster_nodes.master,
+        worker: Vec::new(),
+    };
+
+    let rollout = Rollout::new(RolloutStrategy {
+        parallel: Some(1),
+        fail_fast: true,
+        ..RolloutStrategy::default()
+    });
+    let report = rollout
+        .run(masters.into_nodes().collect(), |role, node| {
+            let sessions = Arc::clone(sessions);
+            let span = tracing::info_span!("renew", role=%role, node_id=%node.id());
+            async move { Certs::new(sessions.session(&node).await?).renew().await }.instrument(span)
+        })
+        .await;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minor_version_of_versions() {
+        assert_eq!(minor_version("v1.32.4"), Some((1, 32)));
+        assert_eq!(minor_version("1.33"), Some((1, 33)));
+        assert_eq!(minor_version("v1"), None);
+        assert_eq!(minor_version("latest"), None);
+    }
+}
@@ -0,0 +1,223 @@
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::{
+    kubernetes::{KubePod, Kubectl},
+    preflight::{Check, CheckStatus},
+    provision::RemoteCommandExecutor,
+};
+
+// busybox newer than 1.28 has a broken nslookup.
+const VERIFY_IMAGE: &str = "busybox:1.28";
+const VERIFY_LABEL: &str = "app=kubeprovision-verify";
+const VERIFY_POD_PREFIX: &str = "kubeprovision-verify-";
+
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Wait until every kube-system pod is Running or Succeeded.
+pub async fn system_pods<E>(kubectl: &Kubectl<E>, deadline: Instant) -> Check
+where
+    E: RemoteCommandExecutor,
+{
+    loop {
+        let pods = match kubectl.pods("kube-system").await {
+            Ok(pods) => pods,
+            Err(err) => return Check::new("system-pods", CheckStatus::Fail, format!("{err:#}")),
+        };
+        let pending = pods
+            .iter()
+            .filter(|pod| pod.phase != "Running" && pod.phase != "Succeeded")
+            .map(|pod| format!("{} ({})", pod.name, pod.phase))
+            .collect::<Vec<_>>();
+
+        if pending.is_empty() {
+            return Check::new(
+                "system-pods",
+                CheckStatus::Pass,
+                format!("{} pods running", pods.len()),
+            );
+        }
+        if Instant::now() >= deadline {
+            return Check::new(
+                "system-pods",
+                CheckStatus::Fail,
+                format!("not running: {}", pending.join(", ")),
+            );
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Run a test pod on each node, then check pod to pod and DNS connectivity from them.
+/// Test pods are deleted afterwards regardless of the result.
+pub async fn workloads<E>(
+    kubectl: &Kubectl<E>,
+    node_names: &[String],
+    deadline: Instant,
+) -> Vec<Check>
+where
+    E: RemoteCommandExecutor,
+{
+    if node_names.is_empty() {
+        return vec![Check::new(
+            "test-pods",
+            CheckStatus::Warn,
+            "no workers to schedule test pods on",
+        )];
+    }
+
+    let checks = run_workloads(kubectl, node_names, deadline).await;
+    if let Err(err) = kubectl.delete_pods(VERIFY_LABEL, None).await {
+        warn!("Could not delete test pods: {err:#}");
+    }
+
+    checks
+}
+
+async fn run_workloads<E>(
+    kubectl: &Kubectl<E>,
+    node_names: &[String],
+    deadline: Instant,
+) -> Vec<Check>
+where
+    E: RemoteCommandExecutor,
+{
+    // left over from an interrupted or previous run. pods keep their names while terminating,
+    // so wait until they are gone before creating them again.
+    let timeout = deadline
+        .saturating_duration_since(Instant::now())
+        .max(Duration::from_secs(1));
+    if let Err(err) = kubectl.delete_pods(VERIFY_LABEL, Some(timeout)).await {
+        return vec![Check::new(
+            "test-pods",
+            CheckStatus::Fail,
+            format!("{err:#}"),
+        )];
+    }
+    for (index, node_name) in node_names.iter().enumerate() {
+        let name = format!("{VERIFY_POD_PREFIX}{index}");
+        if let Err(err) = kubectl
+            .run_pod(
+                &name,
+                node_name,
+                VERIFY_IMAGE,
+                VERIFY_LABEL,
+                &["sleep", "3600"],
+            )
+            .await
+        {
+            return vec![Check::new(
+                "test-pods",
+                CheckStatus::Fail,
+                format!("{err:#}"),
+            )];
+        }
+    }
+
+    let pods = match wait_test_pods(kubectl, node_names.len(), deadline).await {
+        Ok(pods) => pods,
+        Err(check) => return vec![check],
+    };
+    let mut checks = vec![Check::new(
+        "test-pods",
+        CheckStatus::Pass,
+        format!("{} pods ready", pods.len()),
+    )];
+
+    // each pod pings the next one so that traffic crosses nodes.
+    for (index, pod) in pods.iter().enumerate() {
+        let target = &pods[(index + 1) % pods.len()];
+        let ip = target.pod_ip.map(|ip| ip.to_string()).unwrap_or_default();
+        checks.push(
+            exec_check(
+                kubectl,
+                "pod-network",
+                pod,
+                &["ping", "-c", "1", "-W", "2", &ip],
+                &format!("{} -> {} ({ip})", pod.name, target.name),
+            )
+            .await,
+        );
+    }
+    for pod in pods.iter() {
+        checks.push(
+            exec_check(
+                kubectl,
+                "dns",
+                pod,
+                &["nslookup", "kubernetes.default"],
+                &format!("{} -> kubernetes.default", pod.name),
+            )
+            .await,
+        );
+    }
+
+    checks
+}
+
+async fn wait_test_pods<E>(
+    kubectl: &Kubectl<E>,
+    count: usize,
+    deadline: Instant,
+) -> Result<Vec<KubePod>, Check>
+where
+    E: RemoteCommandExecutor,
+{
+    loop {
+        let mut pods = kubectl
+            .pods("default")
+            .await
+            .map_err(|err| Check::new("test-pods", CheckStatus::Fail, format!("{err:#}")))?;
+        pods.retain(|pod| pod.name.starts_with(VERIFY_POD_PREFIX));
+        pods.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let not_ready = pods
+            .iter()
+            .filter(|pod| !pod.ready)
+            .map(|pod| {
+                format!(
+                    "{} on {} ({})",
+                    pod.name,
+                    pod.node_name.as_deref().unwrap_or("-"),
+                    pod.phase
+                )
+            })
+            .collect::<Vec<_>>();
+        if pods.len() == count && not_ready.is_empty() {
+            return Ok(pods);
+        }
+        if Instant::now() >= deadline {
+            return Err(Check::new(
+                "test-pods",
+                CheckStatus::Fail,
+                format!("not ready: {}", not_ready.join(", ")),
+            ));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn exec_check<E>(
+    kubectl: &Kubectl<E>,
+    name: &'static str,
+    pod: &KubePod,
+    command: &[&str],
+    description: &str,
+) -> Check
+where
+    E: RemoteCommandExecutor,
+{
+    match kubectl.exec(&pod.name, command).await {
+        Ok(output) if output.status.success() => Check::new(name, CheckStatus::Pass, description),
+        Ok(output) => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!(
+                "{description}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ),
+        Err(err) => Check::new(name, CheckStatus::Fail, format!("{description}: {err:#}")),
+    }
+}